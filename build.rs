@@ -1,6 +1,8 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_server(false)
+        // lets the generated messages be exported as JSON, and read back from snapshots
+        .type_attribute(".command", "#[derive(serde::Serialize, serde::Deserialize)]")
         .compile_protos(&["proto/command.proto"], &["proto"])?;
     Ok(())
 }
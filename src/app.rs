@@ -0,0 +1,3656 @@
+//! The monitor's state, and how keys, clicks and refreshes change it.
+
+use std::cell::{Ref, RefCell};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{layout::{Constraint, Rect}, style::Style, widgets::TableState};
+use regex::Regex;
+use tokio::sync::mpsc;
+
+use crate::command::Command;
+use crate::config::{AbortAlert, Config, Profile, State};
+use crate::durations::{
+    DurationFormat, TimeCells, TimestampAnomaly, command_durations, command_epochs, command_millis,
+    command_timestamps, epoch_lines, format_duration_ms, is_long_running, normalize_epoch,
+    timestamp_anomalies,
+};
+use crate::export::commands_to_csv;
+use crate::grpc::{
+    Client, Connection, FetchResult, describe_error, fetch_commands, validate_endpoint,
+};
+use crate::snapshot::{DiffKind, Snapshot, write_json};
+use crate::ui::{Theme, ThemeName, property_lines, wrap_name};
+
+const DEFAULT_HIGHLIGHT_SYMBOL: &str = ">> ";
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// How soon the second `g` of `gg` has to follow the first.
+const KEY_SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+pub const DEFAULT_LONG_RUNNING_THRESHOLD_MS: u64 = 60_000;
+const LONG_RUNNING_THRESHOLD_STEP_MS: u64 = 5_000;
+const COLUMN_WIDTH_STEP: u16 = 2;
+const MIN_COLUMN_WIDTH: u16 = 4;
+const MAX_COLUMN_WIDTH: u16 = 120;
+const STATUS_DURATION: Duration = Duration::from_secs(5);
+/// How long the title bar flashes for after a command aborts, with `alert_on_abort` set.
+const ABORT_FLASH_DURATION: Duration = Duration::from_secs(2);
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_millis(1000);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const REFRESH_INTERVAL_STEP: Duration = Duration::from_millis(250);
+/// How many of the latest `get_commands` round trips the latency statistics cover.
+const LATENCY_HISTORY: usize = 20;
+/// How many refreshes the queue depth trend covers, unless configured otherwise.
+const DEFAULT_DEPTH_HISTORY: usize = 60;
+/// How many failed refreshes the error log keeps, dropping the oldest first.
+const ERROR_LOG_CAPACITY: usize = 100;
+/// Size at which `--log-file` is rotated, keeping the previous file alongside with a `.1` suffix.
+const LOG_FILE_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Keeps commands by how long ago their latest activity was, in seconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AgeFilter {
+    YoungerThan(u64),
+    OlderThan(u64),
+}
+
+impl AgeFilter {
+    /// Parses `<N` or `>N`, for younger or older than N seconds, as in its label.
+    pub fn parse(input: &str) -> Option<AgeFilter> {
+        let input = input.trim();
+        let secs = |rest: &str| rest.trim().trim_end_matches('s').parse::<u64>().ok();
+        if let Some(rest) = input.strip_prefix('<') {
+            secs(rest).map(AgeFilter::YoungerThan)
+        } else if let Some(rest) = input.strip_prefix('>') {
+            secs(rest).map(AgeFilter::OlderThan)
+        } else {
+            None
+        }
+    }
+
+    fn matches(self, cmd: &Command, now_ms: u64) -> bool {
+        let age_ms = now_ms.saturating_sub(last_activity_ms(cmd));
+        match self {
+            AgeFilter::YoungerThan(secs) => age_ms < secs * 1000,
+            AgeFilter::OlderThan(secs) => age_ms > secs * 1000,
+        }
+    }
+
+    /// When the command ages past the cutoff, to leave a younger-than filter or join an
+    /// older-than one.
+    fn crossing_ms(self, cmd: &Command) -> u64 {
+        match self {
+            AgeFilter::YoungerThan(secs) => last_activity_ms(cmd) + secs * 1000,
+            AgeFilter::OlderThan(secs) => last_activity_ms(cmd) + secs * 1000 + 1,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            AgeFilter::YoungerThan(secs) => format!("< {secs}s"),
+            AgeFilter::OlderThan(secs) => format!("> {secs}s"),
+        }
+    }
+}
+
+/// How many of the commands are waiting to run.
+fn waiting_count(commands: &[Command]) -> usize {
+    commands.iter().filter(|c| CommandState::parse(&c.state) == CommandState::Waiting).count()
+}
+
+/// When a command last did something: started running if running, was queued if waiting,
+/// or finished if terminated. Otherwise, the most recent of its timestamps.
+pub fn last_activity_ms(cmd: &Command) -> u64 {
+    let (waiting_at, running_at, terminated_at) = command_epochs(cmd);
+    let latest = match CommandState::parse(&cmd.state) {
+        CommandState::Waiting => None,
+        CommandState::Running => running_at,
+        CommandState::Completed | CommandState::Aborted => terminated_at,
+        CommandState::Unknown => terminated_at.or(running_at),
+    };
+    latest.or(waiting_at).unwrap_or(0)
+}
+
+/// A named state filter, as set in the config file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    Default,   // Waiting or Running
+    Waiting,
+    Running,
+    Completed,
+    Aborted,
+    All,
+}
+
+impl FilterMode {
+    /// The states this preset shows.
+    pub fn states(self) -> StateFilter {
+        match self {
+            FilterMode::Default => StateFilter::DEFAULT,
+            FilterMode::Waiting => StateFilter::only(CommandState::Waiting),
+            FilterMode::Running => StateFilter::only(CommandState::Running),
+            FilterMode::Completed => StateFilter::only(CommandState::Completed),
+            FilterMode::Aborted => StateFilter::only(CommandState::Aborted),
+            FilterMode::All => StateFilter::ALL,
+        }
+    }
+}
+
+/// The set of states whose commands are shown. Unknown states are only shown by `ALL`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(from = "Vec<CommandState>", into = "Vec<CommandState>")]
+pub struct StateFilter(u8);
+
+impl StateFilter {
+    pub const DEFAULT: StateFilter =
+        StateFilter(Self::bit(CommandState::Waiting) | Self::bit(CommandState::Running));
+    pub const ALL: StateFilter = StateFilter((1 << CommandState::ALL.len()) - 1);
+
+    const fn bit(state: CommandState) -> u8 {
+        1 << state as u8
+    }
+
+    pub const fn only(state: CommandState) -> StateFilter {
+        StateFilter(Self::bit(state))
+    }
+
+    fn contains(self, state: CommandState) -> bool {
+        self.0 & Self::bit(state) != 0
+    }
+
+    fn toggle(&mut self, state: CommandState) {
+        self.0 ^= Self::bit(state);
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            StateFilter::ALL => "All".to_string(),
+            StateFilter::DEFAULT => "Default (W/R)".to_string(),
+            StateFilter(0) => "None".to_string(),
+            _ => Vec::from(self).iter().map(|s| s.label()).collect::<Vec<_>>().join(" + "),
+        }
+    }
+}
+
+impl Default for StateFilter {
+    fn default() -> StateFilter {
+        StateFilter::DEFAULT
+    }
+}
+
+impl From<Vec<CommandState>> for StateFilter {
+    fn from(states: Vec<CommandState>) -> StateFilter {
+        StateFilter(states.into_iter().fold(0, |bits, state| bits | StateFilter::bit(state)))
+    }
+}
+
+impl From<StateFilter> for Vec<CommandState> {
+    fn from(filter: StateFilter) -> Vec<CommandState> {
+        CommandState::ALL.into_iter().filter(|&state| filter.contains(state)).collect()
+    }
+}
+
+/// A command's state as reported by the server, which sends it as a string.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandState {
+    Waiting,
+    Running,
+    Completed,
+    Aborted,
+    /// Anything else; the raw string is shown as-is.
+    Unknown,
+}
+
+impl CommandState {
+    const ALL: [CommandState; 5] = [
+        CommandState::Waiting,
+        CommandState::Running,
+        CommandState::Completed,
+        CommandState::Aborted,
+        CommandState::Unknown,
+    ];
+
+    pub fn parse(state: &str) -> CommandState {
+        [
+            CommandState::Waiting,
+            CommandState::Running,
+            CommandState::Completed,
+            CommandState::Aborted,
+        ]
+        .into_iter()
+        .find(|s| state.eq_ignore_ascii_case(s.label()))
+        .unwrap_or(CommandState::Unknown)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CommandState::Waiting => "Waiting",
+            CommandState::Running => "Running",
+            CommandState::Completed => "Completed",
+            CommandState::Aborted => "Aborted",
+            CommandState::Unknown => "Unknown",
+        }
+    }
+
+    /// Shown next to the state, whatever the theme, so that color isn't the only cue.
+    pub fn marker(&self) -> char {
+        match self {
+            CommandState::Waiting => '…',
+            CommandState::Running => '▶',
+            CommandState::Completed => '✓',
+            CommandState::Aborted => '✗',
+            CommandState::Unknown => '?',
+        }
+    }
+
+    /// Whether a command in this state has a duration that grows with time.
+    fn is_time_advancing(&self) -> bool {
+        matches!(self, CommandState::Waiting | CommandState::Running)
+    }
+}
+
+/// A column of the commands table, which can be hidden, sorted on or resized.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Id,
+    Name,
+    State,
+    Waiting,
+    Running,
+    Terminated,
+}
+
+impl Column {
+    const ALL: [Column; 6] = [
+        Column::Id,
+        Column::Name,
+        Column::State,
+        Column::Waiting,
+        Column::Running,
+        Column::Terminated,
+    ];
+
+    pub fn is_time_based(&self) -> bool {
+        matches!(self, Column::Waiting | Column::Running | Column::Terminated)
+    }
+
+    /// Cycles through the columns, returning `None` (server order) after the last one.
+    pub fn next(column: Option<Column>) -> Option<Column> {
+        match column {
+            None => Some(Column::Id),
+            Some(Column::Id) => Some(Column::Name),
+            Some(Column::Name) => Some(Column::State),
+            Some(Column::State) => Some(Column::Waiting),
+            Some(Column::Waiting) => Some(Column::Running),
+            Some(Column::Running) => Some(Column::Terminated),
+            Some(Column::Terminated) => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::Name => "Name",
+            Column::State => "State",
+            Column::Waiting => "Waiting",
+            Column::Running => "Running",
+            Column::Terminated => "Completed/Aborted",
+        }
+    }
+
+    pub fn header(&self, absolute_times: bool) -> &'static str {
+        match self {
+            Column::Waiting if absolute_times => "Waiting At",
+            Column::Running if absolute_times => "Running At",
+            Column::Terminated if absolute_times => "Terminated At",
+            _ => self.label(),
+        }
+    }
+
+    /// The column's width unless resized, for shortened IDs in the ID column.
+    fn default_width(&self) -> u16 {
+        match self {
+            Column::Id => 10,
+            Column::Name => 20,
+            Column::State => 12,
+            Column::Waiting | Column::Running => 12,
+            // room for the sort arrow after "Completed/Aborted"
+            Column::Terminated => 19,
+        }
+    }
+
+    /// The column's constraint for a width, which for Name is the least it takes, as it fills
+    /// the space the others leave.
+    pub fn constraint(&self, width: u16) -> Constraint {
+        match self {
+            Column::Name => Constraint::Min(width),
+            _ => Constraint::Length(width),
+        }
+    }
+}
+
+/// The column the timings-by-name table is sorted on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameStatsColumn {
+    Name,
+    Count,
+    Average,
+    Longest,
+}
+
+impl NameStatsColumn {
+    fn next(self) -> NameStatsColumn {
+        match self {
+            NameStatsColumn::Name => NameStatsColumn::Count,
+            NameStatsColumn::Count => NameStatsColumn::Average,
+            NameStatsColumn::Average => NameStatsColumn::Longest,
+            NameStatsColumn::Longest => NameStatsColumn::Name,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NameStatsColumn::Name => "Name",
+            NameStatsColumn::Count => "Count",
+            NameStatsColumn::Average => "Average",
+            NameStatsColumn::Longest => "Longest",
+        }
+    }
+}
+
+pub struct App {
+    pub commands: CommandStore,
+    /// Bumped whenever `commands` changes, or time-based sorts need redoing, to invalidate `view`.
+    view_generation: u64,
+    view: RefCell<ViewCache>,
+    pub table_state: TableState,
+    pub state_filter: StateFilter,
+    pub sort_column: Option<Column>,
+    pub sort_ascending: bool,
+    /// The visible table columns, in their usual order.
+    pub columns: Vec<Column>,
+    /// Widths of the columns that were resized, instead of their defaults.
+    pub column_widths: HashMap<Column, u16>,
+    /// What the search filters by; changes take effect with `search_query_edited`.
+    pub search_query: String,
+    pub searching: bool,
+    /// Set when searching names by regex rather than for a substring: the query compiled, or why
+    /// it doesn't compile.
+    pub search_regex: Option<Result<Regex, String>>,
+    pub age_filter: Option<AgeFilter>,
+    /// The age filter being typed at the prompt, while it is open.
+    pub age_input: Option<String>,
+    /// The endpoint being typed at the prompt, while it is open.
+    pub endpoint_input: Option<String>,
+    /// Set after switching endpoints, until the first refresh from the new one reports back.
+    switching_endpoint: bool,
+    /// The connection profiles from the config file, by name.
+    pub profiles: Vec<(String, Profile)>,
+    /// The profile last switched to, while still connected to it.
+    pub active_profile: Option<String>,
+    pub show_profiles: bool,
+    pub profiles_state: TableState,
+    pub group_by_name: bool,
+    /// Whether each refresh selects the newest command in the view, like `tail -f`. Moving the
+    /// selection by hand turns it off.
+    pub follow: bool,
+    /// Names of the groups collapsed to their header while grouping by name.
+    collapsed_groups: HashSet<String>,
+    pub show_help: bool,
+    pub show_stats: bool,
+    /// Whether the bar chart of commands per state is shown.
+    pub show_state_chart: bool,
+    pub show_name_stats: bool,
+    pub name_stats_state: TableState,
+    pub name_stats_sort: NameStatsColumn,
+    pub name_stats_ascending: bool,
+    pub show_error_log: bool,
+    pub error_log_scroll: u16,
+    pub status: Option<(String, Instant)>,
+    pub selected_command: Option<Command>,
+    pub detail_scroll: u16,
+    /// The index of the property under the cursor in the details view.
+    pub selected_property: usize,
+    /// An action waiting on confirmation in the confirmation dialog.
+    pub pending_action: Option<PendingAction>,
+    pub confirm_quit: bool,
+    pub page_size: usize,
+    pub table_area: Rect,
+    /// Whether keys go to this pane, rather than the other one with `--compare`.
+    pub focused: bool,
+    last_click: Option<(usize, Instant)>,
+    /// When `g` was pressed, if it was the last key, as the start of `gg`.
+    pub pending_g: Option<Instant>,
+    // kept alive so copied text stays available on platforms that serve it from this process
+    clipboard: Option<arboard::Clipboard>,
+    pub client: Option<Client>,
+    pub connection: Connection,
+    pub last_refresh_ok: bool,
+    /// Why the last refresh failed, if it did.
+    pub last_error: Option<String>,
+    pub error_log: VecDeque<(DateTime<Local>, String)>,
+    /// How long the latest `get_commands` calls took, oldest first.
+    pub latencies: VecDeque<Duration>,
+    /// How many commands were waiting after each of the latest refreshes, oldest first.
+    pub depth_history: VecDeque<u64>,
+    /// How many samples `depth_history` keeps.
+    pub depth_history_len: usize,
+    /// How many commands to keep, if not all of them: those that did something last.
+    pub max_commands: Option<usize>,
+    /// How many commands the server reported, of which only `max_commands` may be kept.
+    pub reported_commands: usize,
+    pub log_file: Option<LogFile>,
+    pub last_successful_refresh: Option<Instant>,
+    pub fetching: bool,
+    /// Advances with each frame drawn while fetching, to animate the spinner.
+    pub spinner_frame: usize,
+    fetch_tx: mpsc::UnboundedSender<FetchResult>,
+    fetch_rx: mpsc::UnboundedReceiver<FetchResult>,
+    /// The fetch in flight, if any, to cancel on switching endpoints.
+    fetch_task: Option<tokio::task::JoinHandle<()>>,
+    pub paused: bool,
+    pub refresh_interval: Duration,
+    pub long_running_threshold_ms: u64,
+    pub duration_format: DurationFormat,
+    pub absolute_times: bool,
+    pub full_ids: bool,
+    /// Whether long names wrap onto more lines of their row, instead of being cut short with an
+    /// ellipsis.
+    pub wrap_names: bool,
+    /// The width of the Name column as last drawn, or 0 before the table is drawn or while the
+    /// column is hidden, leaving names whole.
+    pub name_width: u16,
+    last_tick: Instant,
+    /// When the view was frozen, if it is. While frozen, `commands` is left as it was then, and
+    /// fetched commands are kept in `live_commands` instead.
+    pub frozen_at: Option<DateTime<Utc>>,
+    pub live_commands: Option<Vec<Command>>,
+    /// Commands added, or changed in state, by the last refresh, by id, to highlight until the
+    /// next one.
+    pub changes: HashMap<String, DiffKind>,
+    pub alert_on_abort: Option<AbortAlert>,
+    pub theme: ThemeName,
+    pub highlight_symbol: String,
+    /// How the selected row, and the property under the cursor, are drawn, if not as the
+    /// theme has it.
+    selection_style: Option<Style>,
+    /// Set when the terminal bell should ring, for the main loop to do so.
+    pub ring_bell: bool,
+    /// When the title bar started flashing for an aborted command.
+    pub abort_flash: Option<Instant>,
+    /// The snapshot file being reviewed, in which case nothing is fetched.
+    pub replay: Option<std::path::PathBuf>,
+    /// Set when something on screen may have changed, so the next loop redraws.
+    pub dirty: bool,
+}
+
+impl App {
+    pub fn new(endpoint: String) -> App {
+        let (fetch_tx, fetch_rx) = mpsc::unbounded_channel();
+        App {
+            commands: CommandStore::default(),
+            view_generation: 0,
+            view: RefCell::new(ViewCache::default()),
+            table_state: TableState::default(),
+            state_filter: StateFilter::DEFAULT,
+            sort_column: None,
+            sort_ascending: true,
+            column_widths: HashMap::new(),
+            columns: Column::ALL.to_vec(),
+            search_query: String::new(),
+            searching: false,
+            search_regex: None,
+            age_filter: None,
+            age_input: None,
+            endpoint_input: None,
+            switching_endpoint: false,
+            profiles: Vec::new(),
+            active_profile: None,
+            show_profiles: false,
+            profiles_state: TableState::default(),
+            group_by_name: false,
+            collapsed_groups: HashSet::new(),
+            show_help: false,
+            show_stats: false,
+            show_state_chart: false,
+            show_name_stats: false,
+            name_stats_state: TableState::default(),
+            name_stats_sort: NameStatsColumn::Average,
+            name_stats_ascending: false,
+            show_error_log: false,
+            error_log_scroll: 0,
+            status: None,
+            selected_command: None,
+            detail_scroll: 0,
+            selected_property: 0,
+            pending_action: None,
+            confirm_quit: false,
+            page_size: 1,
+            table_area: Rect::default(),
+            focused: true,
+            last_click: None,
+            follow: false,
+            pending_g: None,
+            clipboard: None,
+            client: None,
+            connection: Connection::new(endpoint),
+            last_refresh_ok: false,
+            last_error: None,
+            error_log: VecDeque::with_capacity(ERROR_LOG_CAPACITY),
+            latencies: VecDeque::with_capacity(LATENCY_HISTORY),
+            depth_history: VecDeque::with_capacity(DEFAULT_DEPTH_HISTORY),
+            depth_history_len: DEFAULT_DEPTH_HISTORY,
+            max_commands: None,
+            reported_commands: 0,
+            log_file: None,
+            last_successful_refresh: None,
+            fetching: false,
+            spinner_frame: 0,
+            fetch_tx,
+            fetch_rx,
+            fetch_task: None,
+            paused: false,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            long_running_threshold_ms: DEFAULT_LONG_RUNNING_THRESHOLD_MS,
+            duration_format: DurationFormat::Precise,
+            absolute_times: false,
+            full_ids: false,
+            wrap_names: false,
+            name_width: 0,
+            last_tick: Instant::now(),
+            frozen_at: None,
+            live_commands: None,
+            changes: HashMap::new(),
+            alert_on_abort: None,
+            highlight_symbol: DEFAULT_HIGHLIGHT_SYMBOL.to_string(),
+            theme: ThemeName::default(),
+            selection_style: None,
+            ring_bell: false,
+            abort_flash: None,
+            replay: None,
+            dirty: true,
+        }
+    }
+
+    /// The commands matching the search and filter, sorted by the sort column.
+    pub fn filtered_commands(&self) -> Vec<&Command> {
+        self.view().iter().map(|&i| &self.commands[i]).collect()
+    }
+
+    pub fn filtered_len(&self) -> usize {
+        self.view().len()
+    }
+
+    /// The number of table rows, counting group headers when grouping by name.
+    pub fn row_count(&self) -> usize {
+        self.rows().len()
+    }
+
+    /// The command on the table row at `index`, or `None` for a group header.
+    pub fn row_command(&self, index: usize) -> Option<&Command> {
+        match self.rows().get(index) {
+            Some(&ViewRow::Command(i)) => Some(&self.commands[i]),
+            _ => None,
+        }
+    }
+
+    pub fn selected_group(&self) -> Option<String> {
+        match self.rows().get(self.table_state.selected()?) {
+            Some(ViewRow::Group { name, .. }) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn toggle_grouping(&mut self) {
+        let selected_id = self.selected_id();
+        self.group_by_name = !self.group_by_name;
+        self.restore_selection(selected_id);
+    }
+
+    /// Collapses the group with this name to its header, or expands it again.
+    fn toggle_group(&mut self, name: &str) {
+        if !self.collapsed_groups.remove(name) {
+            self.collapsed_groups.insert(name.to_string());
+        }
+        self.invalidate_view();
+    }
+
+    pub fn set_commands(&mut self, commands: Vec<Command>) {
+        self.commands = CommandStore::from(commands);
+        self.invalidate_view();
+        self.clamp_selection();
+    }
+
+    pub fn invalidate_view(&mut self) {
+        self.view_generation += 1;
+    }
+
+    /// Indices into `commands` of the filtered view, recomputed only when something it depends
+    /// on has changed since it was cached.
+    fn view(&self) -> Ref<'_, [usize]> {
+        Ref::map(self.cached_view(), |view| view.indices.as_slice())
+    }
+
+    /// The rows of the table, which are the filtered view, grouped by name if enabled.
+    pub fn rows(&self) -> Ref<'_, [ViewRow]> {
+        Ref::map(self.cached_view(), |view| view.rows.as_slice())
+    }
+
+    fn cached_view(&self) -> Ref<'_, ViewCache> {
+        let key = ViewKey {
+            generation: self.view_generation,
+            state_filter: self.state_filter,
+            regex_search: self.search_regex.is_some(),
+            age_filter: self.age_filter,
+            sort_column: self.sort_column,
+            sort_ascending: self.sort_ascending,
+            group_by_name: self.group_by_name,
+        };
+        let stale = self.view.borrow().key.as_ref() != Some(&key);
+        if stale {
+            let indices = self.compute_view();
+            let rows = self.compute_rows(&indices);
+            let expires_ms = self.view_expiry(self.now_ms());
+            let computations = self.view.borrow().computations + 1;
+            *self.view.borrow_mut() =
+                ViewCache { key: Some(key), indices, rows, expires_ms, computations };
+        }
+        self.view.borrow()
+    }
+
+    /// Whether time moving on may have changed the view since it was cached.
+    pub fn view_expired(&self) -> bool {
+        self.view_expired_at(self.now_ms())
+    }
+
+    fn view_expired_at(&self, now_ms: u64) -> bool {
+        self.view.borrow().expires_ms.is_some_and(|at| now_ms >= at)
+    }
+
+    /// Drops the cached view if time moving on to `now_ms` may have changed it. Done once a
+    /// frame, rather than reading the clock on every lookup of a row.
+    pub fn expire_view(&mut self, now_ms: u64) {
+        if self.view_expired_at(now_ms) {
+            self.invalidate_view();
+        }
+    }
+
+    /// When a view computed at `now_ms` may go out of date with no change to the commands: when
+    /// the next command crosses the age filter, or, as durations grow and can reorder a sort on
+    /// them, at the next refresh tick. Frozen time never moves on.
+    fn view_expiry(&self, now_ms: u64) -> Option<u64> {
+        if self.frozen_at.is_some() {
+            return None;
+        }
+        let sort_expiry = self
+            .sort_column
+            .is_some_and(|c| c.is_time_based())
+            .then(|| now_ms + self.refresh_interval.as_millis() as u64);
+        let age_expiry = self.age_filter.and_then(|age| {
+            self.commands
+                .iter()
+                .filter(|c| self.state_filter.contains(CommandState::parse(&c.state)))
+                .map(|c| age.crossing_ms(c))
+                .filter(|&at| at > now_ms)
+                .min()
+        });
+        match (sort_expiry, age_expiry) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn compute_view(&self) -> Vec<usize> {
+        let now_ms = self.now_ms();
+        let mut indices: Vec<usize> = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| match &self.search_regex {
+                Some(Ok(regex)) => regex.is_match(&c.name),
+                // an invalid regex is shown at the prompt rather than hiding everything
+                Some(Err(_)) => true,
+                None => command_matches_query(c, &self.search_query),
+            })
+            .filter(|(_, c)| self.state_filter.contains(CommandState::parse(&c.state)))
+            .filter(|(_, c)| self.age_filter.is_none_or(|age| age.matches(c, now_ms)))
+            .map(|(i, _)| i)
+            .collect();
+        if let Some(column) = self.sort_column {
+            let commands = &self.commands;
+            indices.sort_by(|&a, &b| {
+                compare_commands(&commands[a], &commands[b], column, self.sort_ascending, now_ms)
+            });
+        }
+        indices
+    }
+
+    /// Clusters the view under a header per name, in order of each name's first command,
+    /// leaving out the commands of collapsed groups.
+    fn compute_rows(&self, indices: &[usize]) -> Vec<ViewRow> {
+        if !self.group_by_name {
+            return indices.iter().map(|&i| ViewRow::Command(i)).collect();
+        }
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for &i in indices {
+            let name = self.commands[i].name.as_str();
+            let position = *positions.entry(name).or_insert_with(|| {
+                groups.push((name, Vec::new()));
+                groups.len() - 1
+            });
+            groups[position].1.push(i);
+        }
+        let mut rows = Vec::with_capacity(groups.len() + indices.len());
+        for (name, members) in groups {
+            let collapsed = self.collapsed_groups.contains(name);
+            rows.push(ViewRow::Group { name: name.to_string(), count: members.len(), collapsed });
+            if !collapsed {
+                rows.extend(members.into_iter().map(ViewRow::Command));
+            }
+        }
+        rows
+    }
+
+    pub fn next(&mut self) {
+        self.stop_following();
+        let count = self.row_count();
+        if count == 0 {
+            self.table_state.select(None);
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => {
+                if i >= count - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        self.stop_following();
+        let count = self.row_count();
+        if count == 0 {
+            self.table_state.select(None);
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    count - 1
+                } else {
+                    // a selection left behind by a shrinking view moves onto the last row
+                    (i - 1).min(count - 1)
+                }
+            }
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    /// Turns follow mode on, selecting the newest command straight away, or off.
+    pub fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+        if self.follow {
+            self.select_newest();
+        }
+    }
+
+    /// Turns follow mode off when the selection is moved by hand.
+    fn stop_following(&mut self) {
+        if self.follow {
+            self.follow = false;
+            self.set_status("Stopped following, press F to follow again");
+        }
+    }
+
+    /// Selects the command in the view that was queued most recently, the last one shown if
+    /// several were queued at once.
+    fn select_newest(&mut self) {
+        let rows = self.rows();
+        let newest = rows
+            .iter()
+            .enumerate()
+            .filter_map(|(index, row)| match row {
+                &ViewRow::Command(i) => Some((index, normalize_epoch(self.commands[i].waiting_at))),
+                ViewRow::Group { .. } => None,
+            })
+            .max_by_key(|&(_, waiting_at)| waiting_at)
+            .map(|(index, _)| index);
+        drop(rows);
+        if newest.is_some() {
+            self.table_state.select(newest);
+        }
+    }
+
+    pub fn page_down(&mut self) {
+        self.move_selection(self.page_size as isize);
+    }
+
+    pub fn page_up(&mut self) {
+        self.move_selection(-(self.page_size as isize));
+    }
+
+    pub fn first(&mut self) {
+        self.stop_following();
+        self.select_first_row();
+    }
+
+    /// Selects the first row, or nothing when there are no rows, as after the view changes.
+    fn select_first_row(&mut self) {
+        let count = self.row_count();
+        self.table_state.select(if count == 0 { None } else { Some(0) });
+    }
+
+    pub fn last(&mut self) {
+        self.stop_following();
+        let count = self.row_count();
+        self.table_state.select(count.checked_sub(1));
+    }
+
+    /// Switches the search between finding a substring anywhere and matching names by regex.
+    pub fn toggle_regex_search(&mut self) {
+        self.search_regex = match self.search_regex {
+            Some(_) => None,
+            None => Some(compile_search_regex(&self.search_query)),
+        };
+    }
+
+    /// Filters by the search query after it was edited, recompiling it if searching by regex.
+    pub fn search_query_edited(&mut self) {
+        if self.search_regex.is_some() {
+            self.search_regex = Some(compile_search_regex(&self.search_query));
+        }
+        self.invalidate_view();
+    }
+
+    /// Applies the age filter typed at the prompt, clearing it if nothing was typed.
+    pub fn apply_age_input(&mut self) {
+        let Some(input) = self.age_input.take() else {
+            return;
+        };
+        if input.trim().is_empty() {
+            self.age_filter = None;
+        } else if let Some(age) = AgeFilter::parse(&input) {
+            self.age_filter = Some(age);
+        } else {
+            self.set_status(format!("Invalid age filter {input:?}: use <N or >N seconds"));
+            return;
+        }
+        self.select_first_row();
+    }
+
+    /// Connects to the endpoint typed at the prompt, unless it is unchanged or isn't a valid URL,
+    /// which keeps the current connection.
+    pub fn apply_endpoint_input(&mut self) {
+        let Some(input) = self.endpoint_input.take() else {
+            return;
+        };
+        let endpoint = input.trim();
+        if endpoint.is_empty() || endpoint == self.connection.endpoint {
+            return;
+        }
+        if self.replay.is_some() {
+            self.set_status("A replayed snapshot can't connect to an endpoint");
+            return;
+        }
+        if let Err(err) = validate_endpoint(endpoint) {
+            self.set_status(format!("Invalid endpoint {endpoint:?}: {err}"));
+            return;
+        }
+        // the same token and CA certificate, for another endpoint
+        let connection = Connection { endpoint: endpoint.to_string(), ..self.connection.clone() };
+        self.active_profile = None;
+        self.switch_connection(connection);
+    }
+
+    /// Opens the profile picker, on the active profile if there is one.
+    pub fn open_profiles(&mut self) {
+        if self.profiles.is_empty() {
+            self.set_status("No profiles in the config file");
+            return;
+        }
+        let active = self.active_profile.as_ref();
+        let index = self.profiles.iter().position(|(name, _)| Some(name) == active);
+        self.profiles_state.select(Some(index.unwrap_or(0)));
+        self.show_profiles = true;
+    }
+
+    /// Connects with the profile selected in the picker, closing it, unless the profile's
+    /// settings are unusable.
+    pub fn apply_selected_profile(&mut self) {
+        let selected = self.profiles_state.selected().and_then(|i| self.profiles.get(i));
+        let Some((name, profile)) = selected.cloned() else {
+            return;
+        };
+        self.show_profiles = false;
+        if self.replay.is_some() {
+            self.set_status("A replayed snapshot can't connect to an endpoint");
+            return;
+        }
+        match profile.connection(self.connection.request_timeout) {
+            Ok(connection) => {
+                self.active_profile = Some(name);
+                self.switch_connection(connection);
+            }
+            Err(err) => self.set_status(format!("Profile {name}: {err:#}")),
+        }
+    }
+
+    /// Drops the current connection, and its commands, for a new one, cancelling any fetch
+    /// still talking to the old endpoint, then connects.
+    fn switch_connection(&mut self, connection: Connection) {
+        if let Some(task) = self.fetch_task.take() {
+            task.abort();
+        }
+        while self.fetch_rx.try_recv().is_ok() {}
+        self.fetching = false;
+        self.client = None;
+        self.last_refresh_ok = false;
+        self.last_error = None;
+        self.last_successful_refresh = None;
+        self.latencies.clear();
+        self.depth_history.clear();
+        self.reported_commands = 0;
+        self.frozen_at = None;
+        self.live_commands = None;
+        self.set_commands(Vec::new());
+        self.selected_command = None;
+        self.set_status(format!("Connecting to {}...", connection.endpoint));
+        self.connection = connection;
+        self.switching_endpoint = true;
+        self.refresh_commands();
+    }
+
+    /// Jumps to the first row on the second `g` of `gg`. `pending` is when the first was
+    /// pressed, if it was the key just before.
+    pub fn press_g(&mut self, pending: Option<Instant>) {
+        if pending.is_some_and(|at| at.elapsed() < KEY_SEQUENCE_TIMEOUT) {
+            self.first();
+        } else {
+            self.pending_g = Some(Instant::now());
+        }
+    }
+
+    /// Moves the selection by `delta` rows, stopping at the first and last rows.
+    fn move_selection(&mut self, delta: isize) {
+        self.stop_following();
+        let count = self.row_count();
+        if count == 0 {
+            self.table_state.select(None);
+            return;
+        }
+        let current = self.table_state.selected().unwrap_or(0) as isize;
+        let i = (current + delta).clamp(0, count as isize - 1);
+        self.table_state.select(Some(i as usize));
+    }
+
+    /// Shows or hides a table column, keeping at least one shown.
+    pub fn toggle_column(&mut self, column: Column) {
+        if self.columns == [column] {
+            self.set_status("At least one column must be shown");
+            return;
+        }
+        self.columns = Column::ALL
+            .into_iter()
+            .filter(|&c| (c == column) != self.columns.contains(&c))
+            .collect();
+    }
+
+    /// Shows or hides the commands in this state, keeping the others as they are.
+    pub fn toggle_state_filter(&mut self, state: CommandState) {
+        let mut filter = self.state_filter;
+        filter.toggle(state);
+        self.set_state_filter(filter);
+    }
+
+    pub fn set_state_filter(&mut self, filter: StateFilter) {
+        self.state_filter = filter;
+        self.select_first_row();
+    }
+
+    /// Selects the next aborted command after the selection, or the previous one before it,
+    /// wrapping around the view.
+    pub fn jump_to_aborted(&mut self, forward: bool) {
+        self.stop_following();
+        let count = self.row_count();
+        let current = self.table_state.selected().filter(|&i| i < count);
+        let is_aborted = |i: usize| {
+            self.row_command(i)
+                .is_some_and(|c| CommandState::parse(&c.state) == CommandState::Aborted)
+        };
+        let found = (1..=count)
+            .map(|step| match (current, forward) {
+                (Some(i), true) => (i + step) % count,
+                (Some(i), false) => (i + count - step) % count,
+                (None, true) => step - 1,
+                (None, false) => count - step,
+            })
+            .find(|&i| is_aborted(i));
+        match found {
+            Some(i) => self.table_state.select(Some(i)),
+            None => self.set_status("No aborted commands"),
+        }
+    }
+
+    /// Opens the details of the selected command, or collapses / expands the selected group.
+    pub fn open_selected(&mut self) {
+        // rather than do nothing on a selection the rows have shrunk from under
+        self.clamp_selection();
+        if let Some(name) = self.selected_group() {
+            self.toggle_group(&name);
+        } else if let Some(index) = self.table_state.selected()
+            && let Some(cmd) = self.row_command(index)
+        {
+            self.selected_command = Some(cmd.clone());
+            self.detail_scroll = 0;
+            self.selected_property = 0;
+        }
+    }
+
+    /// Moves the cursor in the details view's property list, stopping at either end.
+    pub fn move_property_cursor(&mut self, delta: isize) {
+        let count = self.selected_command.as_ref().map_or(0, |cmd| cmd.properties.len());
+        let last = count.saturating_sub(1) as isize;
+        self.selected_property = (self.selected_property as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Copies the value of the property under the cursor in the details view.
+    fn copy_property_value(&mut self) {
+        let property = self
+            .selected_command
+            .as_ref()
+            .and_then(|cmd| cmd.properties.get(self.selected_property))
+            .cloned();
+        let Some(property) = property else {
+            return;
+        };
+        match self.copy_to_clipboard(&property.value) {
+            Ok(()) => self.set_status(format!("Copied the value of {}", property.name)),
+            Err(err) => self.set_status(format!("Copy failed: {err}")),
+        }
+    }
+
+    /// Moves the open details view to the adjacent command in the current view, stepping over
+    /// group headers.
+    pub fn open_adjacent(&mut self, delta: isize) {
+        let count = self.row_count() as isize;
+        let Some(current) = self.table_state.selected() else {
+            return;
+        };
+        if count == 0 {
+            return;
+        }
+        let current = current as isize;
+        let step = delta.signum();
+        let target = (current + delta).clamp(0, count - 1);
+        let is_header = |i: isize| self.row_command(i as usize).is_none();
+        // step on past headers, or back towards the current row if there are only headers left
+        let mut i = target;
+        while (0..count).contains(&i) && is_header(i) {
+            i += step;
+        }
+        if !(0..count).contains(&i) {
+            i = target;
+            while i != current && is_header(i) {
+                i -= step;
+            }
+        }
+        self.table_state.select(Some(i as usize));
+        self.open_selected();
+    }
+
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.move_selection(1),
+            MouseEventKind::ScrollUp => self.move_selection(-1),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(index) = self.row_at(mouse.column, mouse.row) else {
+                    return;
+                };
+                self.stop_following();
+                self.table_state.select(Some(index));
+                let double_click = self
+                    .last_click
+                    .is_some_and(|(i, at)| i == index && at.elapsed() < DOUBLE_CLICK_INTERVAL);
+                if double_click {
+                    self.last_click = None;
+                    self.open_selected();
+                } else {
+                    self.last_click = Some((index, Instant::now()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Maps a screen position to an index in `rows`, if it lies on a table row.
+    pub fn row_at(&self, column: u16, row: u16) -> Option<usize> {
+        let area = self.table_area;
+        // skip the top border, header row and header margin
+        let body_top = area.y + 3;
+        let body_bottom = (area.y + area.height).saturating_sub(1);
+        if column <= area.x
+            || column + 1 >= area.x + area.width
+            || row < body_top
+            || row >= body_bottom
+        {
+            return None;
+        }
+        // rows with wrapped names take more than one line
+        let mut line = (row - body_top) as usize;
+        let mut index = self.table_state.offset();
+        while index < self.row_count() {
+            let height = self.row_height(index);
+            if line < height {
+                return Some(index);
+            }
+            line -= height;
+            index += 1;
+        }
+        None
+    }
+
+    /// The number of lines the row at `index` takes: more than one only for a command whose name
+    /// wraps.
+    pub fn row_height(&self, index: usize) -> usize {
+        if !self.wrap_names || self.name_width == 0 {
+            return 1;
+        }
+        match self.rows().get(index) {
+            Some(&ViewRow::Command(i)) => wrap_name(&self.commands[i].name, self.name_width).len(),
+            _ => 1,
+        }
+    }
+
+    pub fn selected_id(&self) -> Option<String> {
+        let index = self.table_state.selected()?;
+        self.row_command(index).map(|c| c.id.clone())
+    }
+
+    /// Re-resolves the selected row to the command with the given id, or the nearest index if it
+    /// is gone.
+    pub fn restore_selection(&mut self, id: Option<String>) {
+        let rows = self.rows();
+        let count = rows.len();
+        let position = id.and_then(|id| {
+            rows.iter()
+                .position(|r| matches!(r, &ViewRow::Command(i) if self.commands[i].id == id))
+        });
+        drop(rows);
+        self.select_position(position, count);
+    }
+
+    /// Re-resolves the selected row to the header of the named group, or the nearest index if
+    /// it is gone.
+    fn restore_group_selection(&mut self, name: &str) {
+        let rows = self.rows();
+        let count = rows.len();
+        let position =
+            rows.iter().position(|r| matches!(r, ViewRow::Group { name: n, .. } if n == name));
+        drop(rows);
+        self.select_position(position, count);
+    }
+
+    /// Moves a selection left past the end, once the rows shrink, onto the last row, so it
+    /// always points at a row or is `None`.
+    pub fn clamp_selection(&mut self) {
+        let count = self.row_count();
+        if self.table_state.selected().is_some_and(|i| i >= count) {
+            self.table_state.select(count.checked_sub(1));
+        }
+    }
+
+    fn select_position(&mut self, position: Option<usize>, count: usize) {
+        let index = match position {
+            Some(i) => Some(i),
+            None if count == 0 => None,
+            None => self.table_state.selected().map(|i| i.min(count - 1)),
+        };
+        self.table_state.select(index);
+    }
+
+    /// Formats the Waiting, Running and Completed/Aborted values as durations or timestamps,
+    /// the same way for the table and the details view.
+    pub fn time_cells(&self, cmd: &Command, now_ms: u64) -> TimeCells {
+        let (waiting, running, terminated, termination) =
+            command_durations(cmd, now_ms, self.duration_format);
+        let (mut waiting, mut running, terminated) = if self.absolute_times {
+            command_timestamps(cmd, &Local)
+        } else {
+            (waiting, running, terminated)
+        };
+        // a duration worked out from its own inverted pair of timestamps is clamped to zero, so
+        // mark it; timestamps are shown as they are
+        let anomalies = timestamp_anomalies(cmd);
+        if !self.absolute_times && anomalies.contains(&TimestampAnomaly::RunningBeforeWaiting) {
+            waiting.push('?');
+        }
+        if !self.absolute_times && anomalies.contains(&TimestampAnomaly::TerminatedBeforeRunning) {
+            running.push('?');
+        }
+        TimeCells {
+            waiting,
+            running,
+            terminated,
+            termination,
+            long_running: is_long_running(cmd, self.long_running_threshold_ms, now_ms),
+            anomalies,
+        }
+    }
+
+    pub fn time_unit(&self) -> &'static str {
+        if self.absolute_times { "" } else { self.duration_format.unit() }
+    }
+
+    pub fn increase_long_running_threshold(&mut self) {
+        self.long_running_threshold_ms += LONG_RUNNING_THRESHOLD_STEP_MS;
+        self.show_long_running_threshold();
+    }
+
+    pub fn decrease_long_running_threshold(&mut self) {
+        self.long_running_threshold_ms = self
+            .long_running_threshold_ms
+            .saturating_sub(LONG_RUNNING_THRESHOLD_STEP_MS)
+            .max(LONG_RUNNING_THRESHOLD_STEP_MS);
+        self.show_long_running_threshold();
+    }
+
+    pub fn column_width(&self, column: Column) -> u16 {
+        match self.column_widths.get(&column) {
+            Some(&width) => width,
+            // wide enough for the longest full ID on show
+            None if column == Column::Id && self.full_ids => {
+                full_id_width(&self.filtered_commands())
+            }
+            None => column.default_width(),
+        }
+    }
+
+    /// Widens, or narrows with a negative `delta`, the sort column, or Name in server order.
+    pub fn resize_column(&mut self, delta: i16) {
+        let column = self.sort_column.unwrap_or(Column::Name);
+        let width = self.column_width(column).saturating_add_signed(delta);
+        let width = width.clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH);
+        self.column_widths.insert(column, width);
+        self.set_status(format!("{} column width: {width}", column.label()));
+    }
+
+    /// What is remembered of the view for the next run.
+    pub fn state(&self) -> State {
+        State {
+            state_filter: self.state_filter,
+            sort_column: self.sort_column,
+            sort_ascending: self.sort_ascending,
+            column_widths: self.column_widths.clone(),
+        }
+    }
+
+    /// Restores the view remembered from the last run, leaving out widths outside the allowed
+    /// range.
+    pub fn apply_state(&mut self, state: State) {
+        self.state_filter = state.state_filter;
+        self.sort_column = state.sort_column;
+        self.sort_ascending = state.sort_ascending;
+        self.column_widths = state
+            .column_widths
+            .into_iter()
+            .filter(|(_, width)| (MIN_COLUMN_WIDTH..=MAX_COLUMN_WIDTH).contains(width))
+            .collect();
+    }
+
+    fn show_long_running_threshold(&mut self) {
+        let threshold = format_duration_ms(self.long_running_threshold_ms);
+        self.set_status(format!("Long-running threshold: {threshold} s"));
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        if let Some(ms) = config.refresh_interval_ms {
+            self.refresh_interval =
+                Duration::from_millis(ms).clamp(MIN_REFRESH_INTERVAL, MAX_REFRESH_INTERVAL);
+        }
+        if let Some(filter_mode) = config.filter_mode {
+            self.state_filter = filter_mode.states();
+        }
+        if let Some(ms) = config.long_running_threshold_ms {
+            self.long_running_threshold_ms = ms;
+        }
+        if let Some(len) = config.depth_history {
+            self.depth_history_len = len.max(1);
+        }
+        if let Some(max) = config.max_commands {
+            self.max_commands = Some(max.max(1));
+        }
+        if let Some(confirm_quit) = config.confirm_quit {
+            self.confirm_quit = confirm_quit;
+        }
+        if let Some(alert) = config.alert_on_abort {
+            self.alert_on_abort = Some(alert);
+        }
+        if let Some(columns) = &config.columns
+            && !columns.is_empty()
+        {
+            self.columns = Column::ALL.into_iter().filter(|c| columns.contains(c)).collect();
+        }
+        if let Some(theme) = config.theme {
+            self.theme = theme;
+        }
+        self.profiles = config.profiles.clone().into_iter().collect();
+        if let Some(symbol) = &config.highlight_symbol {
+            self.highlight_symbol = symbol.clone();
+        }
+        if let Some(selection) = &config.selection_style {
+            let (style, warnings) = selection.to_style();
+            self.selection_style = Some(style);
+            if !warnings.is_empty() {
+                self.set_status(format!("Config: {}, using the default", warnings.join(", ")));
+            }
+        }
+    }
+
+    pub fn increase_refresh_interval(&mut self) {
+        self.refresh_interval =
+            (self.refresh_interval + REFRESH_INTERVAL_STEP).min(MAX_REFRESH_INTERVAL);
+    }
+
+    pub fn decrease_refresh_interval(&mut self) {
+        self.refresh_interval = self
+            .refresh_interval
+            .saturating_sub(REFRESH_INTERVAL_STEP)
+            .max(MIN_REFRESH_INTERVAL);
+    }
+
+    pub fn theme(&self) -> Theme {
+        let theme = self.theme.theme();
+        Theme { selection: self.selection_style.unwrap_or(theme.selection), ..theme }
+    }
+
+    pub fn cycle_theme(&mut self) {
+        self.theme = self.theme.next();
+        self.set_status(format!("Theme: {}", self.theme.label()));
+    }
+
+    /// Shows a brief message on the status line.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now()));
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < STATUS_DURATION)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// Copies the full id of the command in the details view, or else the selected row.
+    fn copy_selected_id(&mut self) {
+        let id = match &self.selected_command {
+            Some(cmd) => Some(cmd.id.clone()),
+            None => self.selected_id(),
+        };
+        let Some(id) = id else {
+            return;
+        };
+        match self.copy_to_clipboard(&id) {
+            Ok(()) => self.set_status(format!("Copied {id}")),
+            Err(err) => self.set_status(format!("Copy failed: {err}")),
+        }
+    }
+
+    /// Copies everything shown about the command in the details view, or else writes it to a
+    /// temporary file when there is no clipboard.
+    fn copy_details(&mut self) {
+        let Some(cmd) = &self.selected_command else {
+            return;
+        };
+        let id = cmd.id.clone();
+        let text = self.details_text(cmd, self.now_ms());
+        let Err(err) = self.copy_to_clipboard(&text) else {
+            self.set_status(format!("Copied the details of {id}"));
+            return;
+        };
+        let path = details_path(&id);
+        match std::fs::write(&path, text) {
+            Ok(()) => self.set_status(format!(
+                "No clipboard ({err}), details written to {}",
+                path.display()
+            )),
+            Err(write_err) => self.set_status(format!("Copy failed: {err}; {write_err}")),
+        }
+    }
+
+    /// The details of a command as plain text, for bug reports.
+    pub fn details_text(&self, cmd: &Command, now_ms: u64) -> String {
+        let times = self.time_cells(cmd, now_ms);
+        let unit = self.time_unit();
+        let mut lines = vec![
+            format!("ID: {}", cmd.id),
+            format!("Name: {}", cmd.name),
+            format!("State: {}", cmd.state),
+            format!("Waiting: {}{unit}", times.waiting),
+            format!("Running: {}{unit}", times.running),
+            format!("{}: {}{unit}", times.terminated_label(), times.terminated),
+        ];
+        lines.extend(epoch_lines(cmd, &Local));
+        lines.extend(times.warning());
+        lines.push("Properties:".to_string());
+        let indent = |line: &String| format!("  {}", line.replace('\n', "\n  "));
+        lines.extend(property_lines(cmd).iter().map(indent));
+        lines.join("\n") + "\n"
+    }
+
+    /// Returns whether to quit now, or else asks for confirmation first.
+    pub fn request_quit(&mut self) -> bool {
+        if self.confirm_quit {
+            self.pending_action = Some(PendingAction::Quit);
+        }
+        !self.confirm_quit
+    }
+
+    /// Asks for confirmation to abort the command in the details view, or else the selected row.
+    pub fn request_abort(&mut self) {
+        let cmd = match &self.selected_command {
+            Some(cmd) => Some(cmd.clone()),
+            None => self
+                .table_state
+                .selected()
+                .and_then(|i| self.row_command(i).cloned()),
+        };
+        let Some(cmd) = cmd else {
+            return;
+        };
+        if normalize_epoch(cmd.terminated_at).is_some() {
+            self.set_status(format!("Command {} has already finished", cmd.id));
+        } else {
+            self.pending_action = Some(PendingAction::Abort(cmd.id));
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new()?);
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            clipboard.set_text(text)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the current view to a timestamped CSV file in the working directory.
+    fn export_csv(&mut self) {
+        let now = Local::now();
+        let path = format!("anttpmon-{}.csv", now.format("%Y%m%d-%H%M%S"));
+        let csv = commands_to_csv(&self.filtered_commands(), self.now_ms());
+        match std::fs::write(&path, csv) {
+            Ok(()) => self.set_status(format!("Exported to {path}")),
+            Err(err) => self.set_status(format!("Export failed: {err}")),
+        }
+    }
+
+    /// Saves all commands, frozen or live, to a timestamped snapshot file for `--replay`.
+    fn save_snapshot(&mut self) {
+        let path = format!("anttpmon-snapshot-{}.json", Local::now().format("%Y%m%d-%H%M%S"));
+        let snapshot = Snapshot {
+            captured_at: self.now_ms(),
+            endpoint: self.connection.endpoint.clone(),
+            commands: self.commands.to_vec(),
+        };
+        match write_json(&path, &snapshot) {
+            Ok(()) => self.set_status(format!("Saved snapshot to {path}")),
+            Err(err) => self.set_status(format!("Saving snapshot failed: {err}")),
+        }
+    }
+
+    /// Shows the commands of a saved snapshot, frozen at the time it was captured.
+    pub fn start_replay(&mut self, path: std::path::PathBuf, snapshot: Snapshot) {
+        self.connection.endpoint = snapshot.endpoint;
+        self.frozen_at = Utc.timestamp_millis_opt(snapshot.captured_at as i64).single();
+        self.replay = Some(path);
+        self.set_commands(snapshot.commands);
+        self.select_first_row();
+    }
+
+    /// Writes the full command objects in the current view to a timestamped JSON file.
+    fn export_json(&mut self) {
+        let path = format!("anttpmon-{}.json", Local::now().format("%Y%m%d-%H%M%S"));
+        match write_json(&path, &self.filtered_commands()) {
+            Ok(()) => self.set_status(format!("Exported to {path}")),
+            Err(err) => self.set_status(format!("Export failed: {err}")),
+        }
+    }
+
+    /// Whether the screen needs redrawing: after a change, while timers are counting or the
+    /// spinner is spinning, or to clear a status message that has timed out.
+    pub fn needs_redraw(&mut self) -> bool {
+        if self.status.as_ref().is_some_and(|(_, at)| at.elapsed() >= STATUS_DURATION) {
+            self.status = None;
+            self.dirty = true;
+        }
+        if self.abort_flash.is_some_and(|at| at.elapsed() >= ABORT_FLASH_DURATION) {
+            self.abort_flash = None;
+            self.dirty = true;
+        }
+        self.dirty || self.is_animating() || self.view_expired()
+    }
+
+    /// Whether a prompt, popup or dialog takes the input, rather than the table.
+    pub fn modal_open(&self) -> bool {
+        self.searching
+            || self.age_input.is_some()
+            || self.endpoint_input.is_some()
+            || self.show_help
+            || self.show_stats
+            || self.show_state_chart
+            || self.show_name_stats
+            || self.show_profiles
+            || self.show_error_log
+            || self.pending_action.is_some()
+            || self.selected_command.is_some()
+    }
+
+    /// Whether something on screen moves from frame to frame: timers, or the spinner.
+    pub fn is_animating(&self) -> bool {
+        self.fetching || self.has_running_timers()
+    }
+
+    /// Whether any command is waiting or running, so its durations advance on screen.
+    pub fn has_running_timers(&self) -> bool {
+        self.frozen_at.is_none()
+            && self.commands.iter().any(|c| CommandState::parse(&c.state).is_time_advancing())
+    }
+
+    /// The time durations are measured up to: now, or when the view was frozen.
+    pub fn now_ms(&self) -> u64 {
+        self.frozen_at.unwrap_or_else(Utc::now).timestamp_millis() as u64
+    }
+
+    /// Freezes the view on the current commands, or goes back to the live ones.
+    pub fn toggle_freeze(&mut self) {
+        if self.replay.is_some() {
+            self.set_status("A replayed snapshot is always frozen");
+        } else if self.frozen_at.take().is_some() {
+            if let Some(commands) = self.live_commands.take() {
+                self.replace_commands(commands);
+            }
+        } else {
+            self.frozen_at = Some(Utc::now());
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some() && self.last_refresh_ok
+    }
+
+    /// Whether it is time to fetch the commands: when automatic refresh is on.
+    pub fn refresh_due(&self) -> Option<Duration> {
+        (!self.paused).then(|| self.refresh_interval.saturating_sub(self.last_tick.elapsed()))
+    }
+
+    /// Starts fetching commands in the background, unless a fetch is already in flight.
+    pub fn refresh_commands(&mut self) {
+        self.last_tick = Instant::now();
+        if self.fetching || self.replay.is_some() {
+            return;
+        }
+        self.fetching = true;
+        self.dirty = true;
+        let tx = self.fetch_tx.clone();
+        let client = self.client.clone();
+        let connection = self.connection.clone();
+        self.fetch_task = Some(tokio::spawn(async move {
+            let _ = tx.send(fetch_commands(client, &connection).await);
+        }));
+    }
+
+    /// Applies any completed fetches without waiting on the network.
+    pub fn poll_refresh(&mut self) {
+        while let Ok(fetch) = self.fetch_rx.try_recv() {
+            let _ = self.apply_refresh(fetch);
+            self.dirty = true;
+        }
+    }
+
+    /// Adds a sample to the queue depth trend, dropping the oldest once it is full.
+    fn record_depth(&mut self, waiting: usize) {
+        while self.depth_history.len() >= self.depth_history_len {
+            self.depth_history.pop_front();
+        }
+        self.depth_history.push_back(waiting as u64);
+    }
+
+    pub fn log_error(&mut self, message: String) {
+        if self.error_log.len() == ERROR_LOG_CAPACITY {
+            self.error_log.pop_front();
+        }
+        self.error_log.push_back((Local::now(), message));
+    }
+
+    /// Records a refresh in the log file, if any: failures in full, and changes in connection.
+    fn log_refresh(&self, result: &Result<Vec<Command>>) {
+        let Some(log_file) = &self.log_file else {
+            return;
+        };
+        let endpoint = &self.connection.endpoint;
+        match result {
+            Ok(_) if !self.last_refresh_ok => {
+                let event =
+                    if self.last_successful_refresh.is_some() { "Reconnected" } else { "Connected" };
+                log_file.write(&format!("{event} to {endpoint}"));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                if self.last_refresh_ok {
+                    log_file.write(&format!("Lost connection to {endpoint}"));
+                }
+                log_file.write(&format!("Refresh failed: {err:?}"));
+            }
+        }
+    }
+
+    /// Shows new commands, keeping the same command or group selected, and highlighting those
+    /// that are new or changed state, unless these are the first commands fetched.
+    pub fn replace_commands(&mut self, commands: Vec<Command>) {
+        self.update_store(|store| store.merge(commands));
+    }
+
+    /// Changes the commands, however they came in, then highlights what changed and keeps the
+    /// selection and details view on the same commands.
+    fn update_store(&mut self, update: impl FnOnce(&mut CommandStore) -> StoreChanges) {
+        let selected_id = self.selected_id();
+        let selected_group = self.selected_group();
+        let changes = update(&mut self.commands);
+        self.invalidate_view();
+        self.changes = if self.last_successful_refresh.is_some() {
+            changes.highlights()
+        } else {
+            HashMap::new()
+        };
+        self.alert_new_aborts();
+        // keep the details view up to date with the command it shows
+        if let Some(open) = &self.selected_command {
+            if let Some(latest) = self.commands.get(&open.id) {
+                self.selected_command = Some(latest.clone());
+            } else if changes.removed.contains(&open.id) {
+                let message = format!("Command {} is no longer reported by the server", open.id);
+                self.set_status(message);
+            }
+        }
+        match selected_group {
+            Some(name) => self.restore_group_selection(&name),
+            None => self.restore_selection(selected_id),
+        }
+        if self.follow {
+            self.select_newest();
+        }
+    }
+
+    /// Alerts, if enabled, to the commands the last refresh found newly aborted.
+    fn alert_new_aborts(&mut self) {
+        let aborted: Vec<&str> = self
+            .changes
+            .keys()
+            .filter_map(|id| self.commands.get(id))
+            .filter(|c| CommandState::parse(&c.state) == CommandState::Aborted)
+            .map(|c| c.id.as_str())
+            .collect();
+        if let Some(alert) = self.alert_on_abort
+            && !aborted.is_empty()
+        {
+            let message = match aborted.as_slice() {
+                [id] => format!("Command {id} aborted"),
+                ids => format!("{} commands aborted", ids.len()),
+            };
+            self.ring_bell = alert.bell();
+            if alert.flash() {
+                self.abort_flash = Some(Instant::now());
+            }
+            self.set_status(message);
+        }
+    }
+
+    pub fn apply_refresh(&mut self, fetch: FetchResult) -> Result<()> {
+        self.fetching = false;
+        self.client = fetch.client;
+        if std::mem::take(&mut self.switching_endpoint) {
+            let endpoint = &self.connection.endpoint;
+            self.set_status(match &fetch.result {
+                Ok(_) => format!("Connected to {endpoint}"),
+                Err(err) => format!("Failed to connect to {endpoint}: {}", describe_error(err)),
+            });
+        }
+        if let Some(latency) = fetch.latency {
+            if self.latencies.len() == LATENCY_HISTORY {
+                self.latencies.pop_front();
+            }
+            self.latencies.push_back(latency);
+        }
+        self.log_refresh(&fetch.result);
+        match fetch.result {
+            Ok(mut commands) => {
+                // the trend follows the server, even while the table is frozen
+                self.record_depth(waiting_count(&commands));
+                self.reported_commands = commands.len();
+                if let Some(max) = self.max_commands {
+                    retain_recent(&mut commands, max);
+                }
+                if self.frozen_at.is_some() {
+                    self.live_commands = Some(commands);
+                } else {
+                    self.replace_commands(commands);
+                }
+                self.last_refresh_ok = true;
+                self.last_error = None;
+                self.last_successful_refresh = Some(Instant::now());
+                Ok(())
+            }
+            Err(err) => {
+                self.last_refresh_ok = false;
+                let message = describe_error(&err);
+                self.log_error(message.clone());
+                self.last_error = Some(message);
+                Err(err)
+            }
+        }
+    }
+
+    /// Acts on a key pressed in this pane, returning what is left for the event loop to do.
+    pub fn handle_key(&mut self, key: KeyCode) -> Option<Action> {
+        // the dialog takes all input until answered, even over the overlays
+        if let Some(action) = self.pending_action.take() {
+            match key {
+                KeyCode::Char('y') => return Some(action.confirmed()),
+                KeyCode::Char('q') if action == PendingAction::Quit => return Some(Action::Quit),
+                KeyCode::Char('n') | KeyCode::Esc => {}
+                _ => self.pending_action = Some(action),
+            }
+            return None;
+        }
+
+        if self.show_help {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('?') | KeyCode::Esc => self.show_help = false,
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.show_stats {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('m') | KeyCode::Esc => self.show_stats = false,
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.show_state_chart {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('B') | KeyCode::Esc => self.show_state_chart = false,
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.show_profiles {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('U') | KeyCode::Esc => self.show_profiles = false,
+                KeyCode::Down | KeyCode::Char('j') => self.profiles_state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.profiles_state.select_previous(),
+                KeyCode::Enter => self.apply_selected_profile(),
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.show_name_stats {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('T') | KeyCode::Esc => self.show_name_stats = false,
+                KeyCode::Char('s') => self.name_stats_sort = self.name_stats_sort.next(),
+                KeyCode::Char('S') => self.name_stats_ascending = !self.name_stats_ascending,
+                KeyCode::Down | KeyCode::Char('j') => self.name_stats_state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.name_stats_state.select_previous(),
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.show_error_log {
+            match key {
+                KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+                KeyCode::Char('l') | KeyCode::Esc => self.show_error_log = false,
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.error_log_scroll = self.error_log_scroll.saturating_add(1);
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.error_log_scroll = self.error_log_scroll.saturating_sub(1);
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        if let Some(input) = &mut self.endpoint_input {
+            match key {
+                KeyCode::Enter => self.apply_endpoint_input(),
+                KeyCode::Esc => self.endpoint_input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return None;
+        }
+
+        if let Some(input) = &mut self.age_input {
+            match key {
+                KeyCode::Enter => self.apply_age_input(),
+                KeyCode::Esc => self.age_input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return None;
+        }
+
+        if self.searching {
+            match key {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search_query.clear();
+                }
+                KeyCode::Backspace => {
+                    self.search_query.pop();
+                }
+                KeyCode::Char(c) => self.search_query.push(c),
+                KeyCode::Tab => self.toggle_regex_search(),
+                _ => {}
+            }
+            self.search_query_edited();
+            self.select_first_row();
+            return None;
+        }
+
+        if self.selected_command.is_some() {
+            match key {
+                KeyCode::Enter | KeyCode::Esc | KeyCode::Backspace => {
+                    self.selected_command = None;
+                }
+                KeyCode::Right | KeyCode::Char('n') => self.open_adjacent(1),
+                KeyCode::Left | KeyCode::Char('p') => self.open_adjacent(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.move_property_cursor(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_property_cursor(-1),
+                KeyCode::Char('v') => self.copy_property_value(),
+                KeyCode::Char('y') => self.copy_selected_id(),
+                KeyCode::Char('Y') => self.copy_details(),
+                KeyCode::Char('x') => self.request_abort(),
+                _ => {}
+            }
+            return None;
+        }
+
+        let pending_g = self.pending_g.take();
+        match key {
+            KeyCode::Char('q') if self.request_quit() => return Some(Action::Quit),
+            KeyCode::Char('?') => self.show_help = true,
+            KeyCode::Char('m') => self.show_stats = true,
+            KeyCode::Char('B') => self.show_state_chart = true,
+            KeyCode::Char('T') => {
+                self.show_name_stats = true;
+                self.name_stats_state.select(Some(0));
+            }
+            KeyCode::Char('l') => {
+                self.show_error_log = true;
+                self.error_log_scroll = 0;
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.next(),
+            KeyCode::Up | KeyCode::Char('k') => self.previous(),
+            KeyCode::PageDown => self.page_down(),
+            KeyCode::PageUp => self.page_up(),
+            KeyCode::Home => self.first(),
+            KeyCode::End => self.last(),
+            KeyCode::Char('g') => self.press_g(pending_g),
+            KeyCode::Char('G') => self.last(),
+            KeyCode::Char('N') => self.jump_to_aborted(true),
+            KeyCode::Char('P') => self.jump_to_aborted(false),
+            KeyCode::F(5) => self.refresh_commands(),
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('C') => self.cycle_theme(),
+            KeyCode::Char('A') => {
+                self.age_input = Some(self.age_filter.map(AgeFilter::label).unwrap_or_default());
+            }
+            KeyCode::Char('u') => self.endpoint_input = Some(self.connection.endpoint.clone()),
+            KeyCode::Char('U') => self.open_profiles(),
+            KeyCode::Char('y') => self.copy_selected_id(),
+            KeyCode::Char('x') => self.request_abort(),
+            KeyCode::Char('e') => self.export_csv(),
+            KeyCode::Char('E') => self.export_json(),
+            KeyCode::Char('W') => self.save_snapshot(),
+            KeyCode::Char('p') => self.paused = !self.paused,
+            KeyCode::Char('f') => self.toggle_freeze(),
+            KeyCode::Char('F') => self.toggle_follow(),
+            KeyCode::Char('+') => self.increase_refresh_interval(),
+            KeyCode::Char('-') => self.decrease_refresh_interval(),
+            KeyCode::Char('h') => self.duration_format = self.duration_format.toggle(),
+            KeyCode::Char('t') => self.absolute_times = !self.absolute_times,
+            KeyCode::Char('i') => self.full_ids = !self.full_ids,
+            KeyCode::Char('o') => self.wrap_names = !self.wrap_names,
+            KeyCode::Char(c @ '1'..='6') => {
+                self.toggle_column(Column::ALL[c as usize - '1' as usize]);
+            }
+            KeyCode::Char(']') => self.increase_long_running_threshold(),
+            KeyCode::Char('[') => self.decrease_long_running_threshold(),
+            KeyCode::Char('s') => self.sort_column = Column::next(self.sort_column),
+            KeyCode::Char('S') => self.sort_ascending = !self.sort_ascending,
+            KeyCode::Char('<') => self.resize_column(-(COLUMN_WIDTH_STEP as i16)),
+            KeyCode::Char('>') => self.resize_column(COLUMN_WIDTH_STEP as i16),
+            KeyCode::Enter => self.open_selected(),
+            KeyCode::Char('z') => self.toggle_grouping(),
+            KeyCode::Char('w') => self.toggle_state_filter(CommandState::Waiting),
+            KeyCode::Char('r') => self.toggle_state_filter(CommandState::Running),
+            KeyCode::Char('c') => self.toggle_state_filter(CommandState::Completed),
+            KeyCode::Char('b') => self.toggle_state_filter(CommandState::Aborted),
+            KeyCode::Char('a') => self.set_state_filter(StateFilter::ALL),
+            KeyCode::Char('d') => self.set_state_filter(StateFilter::DEFAULT),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// An action that needs confirming before it runs, such as one changing server state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PendingAction {
+    /// Abort the command with this id.
+    Abort(String),
+    Quit,
+}
+
+impl PendingAction {
+    pub fn prompt(&self) -> String {
+        match self {
+            PendingAction::Abort(id) => format!("Abort command {id}?"),
+            PendingAction::Quit => "Quit AntTP Monitor?".to_string(),
+        }
+    }
+
+    /// Describes what `y` does in the dialog.
+    pub fn confirm_label(&self) -> &'static str {
+        match self {
+            PendingAction::Abort(_) => "abort",
+            PendingAction::Quit => "quit",
+        }
+    }
+
+    /// What is left for the event loop to do once the action is confirmed.
+    fn confirmed(self) -> Action {
+        match self {
+            PendingAction::Abort(id) => Action::Abort(id),
+            PendingAction::Quit => Action::Quit,
+        }
+    }
+}
+
+/// What a key asks of the event loop, for what the app can't do on its own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    /// Ask the server to abort the command with this id.
+    Abort(String),
+}
+
+/// Appends timestamped lines to a file, rotating it once it grows past a size limit. Failing to
+/// write is ignored, as logging must never interrupt monitoring.
+pub struct LogFile {
+    pub path: std::path::PathBuf,
+    pub max_bytes: u64,
+}
+
+impl LogFile {
+    pub fn new(path: std::path::PathBuf) -> LogFile {
+        LogFile { path, max_bytes: LOG_FILE_MAX_BYTES }
+    }
+
+    pub fn write(&self, message: &str) {
+        let _ = self.try_write(message);
+    }
+
+    fn try_write(&self, message: &str) -> io::Result<()> {
+        if std::fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            std::fs::rename(&self.path, rotated)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        io::Write::write_all(&mut file, format!("{timestamp} {message}\n").as_bytes())
+    }
+}
+
+/// The fetched commands in server order, indexed by id so that each refresh can be merged in,
+/// telling which commands came, went or changed state.
+#[derive(Default)]
+pub struct CommandStore {
+    commands: Vec<Command>,
+    positions: HashMap<String, usize>,
+}
+
+/// What a refresh changed, by command id.
+#[derive(Debug, Default, PartialEq)]
+pub struct StoreChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub state_changed: Vec<String>,
+}
+
+impl StoreChanges {
+    /// The commands to highlight: new ones, and those that changed state.
+    fn highlights(&self) -> HashMap<String, DiffKind> {
+        let added = self.added.iter().map(|id| (id.clone(), DiffKind::Added));
+        let changed = self.state_changed.iter().map(|id| (id.clone(), DiffKind::Changed));
+        added.chain(changed).collect()
+    }
+}
+
+impl CommandStore {
+    pub fn get(&self, id: &str) -> Option<&Command> {
+        self.positions.get(id).map(|&i| &self.commands[i])
+    }
+
+    /// Takes on the commands of a refresh, in their new order, reporting what changed.
+    pub fn merge(&mut self, fetched: Vec<Command>) -> StoreChanges {
+        let positions = index_by_id(&fetched);
+        let mut changes = StoreChanges::default();
+        for cmd in &fetched {
+            match self.get(&cmd.id) {
+                None => changes.added.push(cmd.id.clone()),
+                Some(old) if !old.state.eq_ignore_ascii_case(&cmd.state) => {
+                    changes.state_changed.push(cmd.id.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        changes.removed = self
+            .commands
+            .iter()
+            .filter(|c| !positions.contains_key(&c.id))
+            .map(|c| c.id.clone())
+            .collect();
+        self.commands = fetched;
+        self.positions = positions;
+        changes
+    }
+}
+
+/// Keeps the `max` commands that did something last, dropping the rest but keeping the order.
+pub fn retain_recent(commands: &mut Vec<Command>, max: usize) {
+    if commands.len() <= max {
+        return;
+    }
+    let mut latest: Vec<usize> = (0..commands.len()).collect();
+    latest.sort_by_key(|&i| Reverse(last_activity_ms(&commands[i])));
+    let mut keep = vec![false; commands.len()];
+    for &i in &latest[..max] {
+        keep[i] = true;
+    }
+    let mut keep = keep.into_iter();
+    commands.retain(|_| keep.next().unwrap_or(false));
+}
+
+fn index_by_id(commands: &[Command]) -> HashMap<String, usize> {
+    commands.iter().enumerate().map(|(i, c)| (c.id.clone(), i)).collect()
+}
+
+impl From<Vec<Command>> for CommandStore {
+    fn from(commands: Vec<Command>) -> CommandStore {
+        CommandStore { positions: index_by_id(&commands), commands }
+    }
+}
+
+impl Deref for CommandStore {
+    type Target = [Command];
+
+    fn deref(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+/// What the cached filtered view was computed from.
+#[derive(PartialEq)]
+struct ViewKey {
+    generation: u64,
+    state_filter: StateFilter,
+    regex_search: bool,
+    age_filter: Option<AgeFilter>,
+    sort_column: Option<Column>,
+    sort_ascending: bool,
+    group_by_name: bool,
+}
+
+#[derive(Default)]
+struct ViewCache {
+    key: Option<ViewKey>,
+    indices: Vec<usize>,
+    rows: Vec<ViewRow>,
+    /// When time moving on may change the view, if it can.
+    expires_ms: Option<u64>,
+    /// How many times the view has been computed.
+    computations: u64,
+}
+
+/// A row of the table: a command, given by its index in `App::commands`, or a group header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViewRow {
+    Group { name: String, count: usize, collapsed: bool },
+    Command(usize),
+}
+
+pub fn format_id(id: &str) -> String {
+    let count = id.chars().count();
+    if count <= 6 {
+        id.to_string()
+    } else {
+        let head: String = id.chars().take(3).collect();
+        let tail: String = id.chars().skip(count - 3).collect();
+        format!("{head}..{tail}")
+    }
+}
+
+/// Where `Y` writes a command's details without a clipboard: a file in the temp directory named
+/// after the id, with anything but ASCII letters, digits, `_` and `-` replaced, as the id comes
+/// from the server and could otherwise lead out of the directory.
+fn details_path(id: &str) -> std::path::PathBuf {
+    let id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    std::env::temp_dir().join(format!("anttpmon-{id}.txt"))
+}
+
+/// Compiles a regex search query, or describes what is wrong with it in a line.
+fn compile_search_regex(query: &str) -> Result<Regex, String> {
+    Regex::new(query).map_err(|e| {
+        // syntax errors draw a caret under the pattern, before the message on the last line
+        let message = e.to_string();
+        let last = message.lines().last().unwrap_or_default();
+        last.strip_prefix("error: ").unwrap_or(last).to_string()
+    })
+}
+
+/// Case-insensitively matches the query against the full ID, name, and property names and values.
+pub fn command_matches_query(cmd: &Command, query: &str) -> bool {
+    if query.is_empty() {
+        return true;
+    }
+    let query = query.to_lowercase();
+    let matches = |s: &str| s.to_lowercase().contains(&query);
+    matches(&cmd.id)
+        || matches(&cmd.name)
+        || cmd.properties.iter().any(|p| matches(&p.name) || matches(&p.value))
+}
+
+fn compare_commands(
+    a: &Command,
+    b: &Command,
+    column: Column,
+    ascending: bool,
+    now_ms: u64,
+) -> Ordering {
+    let millis = |c: &Command| command_millis(c, now_ms);
+    let directed = |ordering: Ordering| if ascending { ordering } else { ordering.reverse() };
+    match column {
+        Column::Id => directed(a.id.cmp(&b.id)),
+        Column::Name => directed(a.name.cmp(&b.name)),
+        Column::State => directed(a.state.cmp(&b.state)),
+        Column::Waiting => compare_durations(millis(a).0, millis(b).0, ascending),
+        Column::Running => compare_durations(millis(a).1, millis(b).1, ascending),
+        Column::Terminated => compare_durations(millis(a).2, millis(b).2, ascending),
+    }
+}
+
+/// Orders durations numerically, always placing missing values last regardless of direction.
+pub fn compare_durations(a: Option<u64>, b: Option<u64>, ascending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if ascending => a.cmp(&b),
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Width of the ID column when showing full ids, fitting the longest one plus padding.
+pub fn full_id_width(commands: &[&Command]) -> u16 {
+    let longest = commands.iter().map(|c| c.id.chars().count()).max().unwrap_or(0);
+    (longest.max("ID".len()) + 2) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use ratatui::style::{Color, Modifier};
+    use crate::DEFAULT_ENDPOINT;
+    use crate::command::{Property, command};
+    use crate::ui::{SelectionStyle, retained_summary, title};
+
+    fn app_with_commands(count: usize) -> App {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let commands = (0..count).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect();
+        app.set_commands(commands);
+        app.table_state.select(Some(0));
+        app
+    }
+
+    #[test]
+    fn test_handle_key_navigation() {
+        let mut app = app_with_commands(3);
+        assert_eq!(app.handle_key(KeyCode::Down), None);
+        assert_eq!(app.table_state.selected(), Some(1));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        // wraps around past the last row
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::Char('G'));
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.handle_key(KeyCode::Char('g'));
+        app.handle_key(KeyCode::Char('g'));
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_next_and_previous_at_the_ends() {
+        let mut app = app_with_commands(3);
+        app.previous();
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.next();
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        // a single row is both the first and the last
+        let mut app = app_with_commands(1);
+        app.next();
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.previous();
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        let mut app = app_with_commands(0);
+        app.next();
+        assert_eq!(app.table_state.selected(), None);
+        app.previous();
+        assert_eq!(app.table_state.selected(), None);
+
+        // a selection past the end, left by a shrinking view, moves back within it
+        let mut app = app_with_commands(3);
+        app.table_state.select(Some(7));
+        app.previous();
+        assert_eq!(app.table_state.selected(), Some(2));
+        app.table_state.select(Some(7));
+        app.next();
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_selection_stays_valid_when_rows_shrink() {
+        let mut app = app_with_commands(5);
+        app.table_state.select(Some(4));
+        // the selected command is gone after a refresh, so the nearest row is selected
+        let remaining = (0..2).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect();
+        app.replace_commands(remaining);
+        assert_eq!(app.table_state.selected(), Some(1));
+
+        // a selection going stale some other way is clamped before Enter opens it
+        app.table_state.select(Some(4));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.table_state.selected(), Some(1));
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("1"));
+        app.handle_key(KeyCode::Esc);
+
+        app.set_commands(Vec::new());
+        assert_eq!(app.table_state.selected(), None);
+        assert_eq!(app.handle_key(KeyCode::Enter), None);
+        assert!(app.selected_command.is_none());
+    }
+
+    #[test]
+    fn test_view_is_computed_once_per_change() {
+        let mut app = app_with_commands(1_000);
+        let computations = |app: &App| app.view.borrow().computations;
+        app.filtered_len();
+        let first = computations(&app);
+        for _ in 0..100 {
+            app.filtered_len();
+            app.row_count();
+            app.row_command(500);
+        }
+        assert_eq!(computations(&app), first);
+
+        // changing a filter, or the commands, computes it again, once
+        app.search_query = "1".to_string();
+        app.search_query_edited();
+        app.filtered_len();
+        app.row_count();
+        assert_eq!(computations(&app), first + 1);
+        app.set_commands(Vec::new());
+        app.filtered_len();
+        app.row_count();
+        assert_eq!(computations(&app), first + 2);
+    }
+
+    #[test]
+    fn test_view_expires_only_when_time_can_change_it() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let now_ms = app.now_ms();
+        app.set_commands(vec![
+            command("old", "cmd", now_ms - 50_000, None),
+            command("new", "cmd", now_ms - 10_000, None),
+        ]);
+        assert_eq!(app.filtered_len(), 2);
+        assert!(!app.view_expired());
+        assert_eq!(app.view_expiry(now_ms), None);
+
+        // the view holds until the newest command ages out of the filter
+        app.age_filter = Some(AgeFilter::YoungerThan(30));
+        assert_eq!(app.filtered_len(), 1);
+        assert_eq!(app.view_expiry(now_ms), Some(now_ms + 20_000));
+        assert!(!app.view_expired());
+        app.age_filter = Some(AgeFilter::OlderThan(30));
+        assert_eq!(app.view_expiry(now_ms), Some(now_ms + 20_001));
+
+        // a sort on durations is brought up to date at each refresh tick
+        app.age_filter = None;
+        app.sort_column = Some(Column::Waiting);
+        let tick_ms = app.refresh_interval.as_millis() as u64;
+        assert_eq!(app.view_expiry(now_ms), Some(now_ms + tick_ms));
+
+        // an expired view is computed again once the frame drops it, not on every lookup
+        app.filtered_len();
+        let computations = app.view.borrow().computations;
+        let expires_ms = app.view.borrow().expires_ms.unwrap();
+        app.expire_view(expires_ms - 1);
+        app.filtered_len();
+        assert_eq!(app.view.borrow().computations, computations);
+        app.expire_view(expires_ms);
+        app.filtered_len();
+        assert_eq!(app.view.borrow().computations, computations + 1);
+
+        app.sort_column = Some(Column::Name);
+        assert_eq!(app.view_expiry(now_ms), None);
+
+        app.sort_column = Some(Column::Waiting);
+        app.frozen_at = Some(Utc::now());
+        assert_eq!(app.view_expiry(now_ms), None);
+    }
+
+    #[test]
+    fn test_filter_switch_clamps_selection() {
+        let mut app = app_with_commands(2);
+        app.table_state.select(Some(1));
+        // showing only aborted commands, of which there are none
+        app.toggle_state_filter(CommandState::Waiting);
+        app.toggle_state_filter(CommandState::Running);
+        app.toggle_state_filter(CommandState::Aborted);
+        assert_eq!(app.filtered_len(), 0);
+        assert_eq!(app.table_state.selected(), None);
+        app.set_state_filter(StateFilter::DEFAULT);
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        // the same goes for a search matching nothing
+        app.handle_key(KeyCode::Char('/'));
+        app.handle_key(KeyCode::Char('z'));
+        assert_eq!(app.table_state.selected(), None);
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_handle_key_filters() {
+        let mut app = app_with_commands(1);
+        app.handle_key(KeyCode::Char('w'));
+        assert!(!app.state_filter.contains(CommandState::Waiting));
+        assert_eq!(app.filtered_len(), 0);
+        app.handle_key(KeyCode::Char('a'));
+        assert_eq!(app.state_filter, StateFilter::ALL);
+        app.handle_key(KeyCode::Char('d'));
+        assert_eq!(app.state_filter, StateFilter::DEFAULT);
+        assert_eq!(app.filtered_len(), 1);
+    }
+
+    #[test]
+    fn test_handle_key_popups() {
+        let mut app = app_with_commands(2);
+        app.handle_key(KeyCode::Char('?'));
+        assert!(app.show_help);
+        // keys meant for the table don't reach it under a popup
+        app.handle_key(KeyCode::Down);
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::Esc);
+        assert!(!app.show_help);
+
+        app.handle_key(KeyCode::Char('m'));
+        assert!(app.show_stats);
+        app.handle_key(KeyCode::Char('m'));
+        assert!(!app.show_stats);
+
+        app.handle_key(KeyCode::Char('B'));
+        assert!(app.show_state_chart);
+        app.handle_key(KeyCode::Up);
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::Esc);
+        assert!(!app.show_state_chart);
+
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("0"));
+        app.handle_key(KeyCode::Char('n'));
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("1"));
+        app.handle_key(KeyCode::Esc);
+        assert!(app.selected_command.is_none());
+    }
+
+    #[test]
+    fn test_handle_key_search_prompt() {
+        let mut app = app_with_commands(2);
+        app.handle_key(KeyCode::Char('/'));
+        for c in "1j".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        // nothing matches, so nothing is selected
+        assert_eq!(app.search_query, "1j");
+        assert_eq!(app.table_state.selected(), None);
+        app.handle_key(KeyCode::Backspace);
+        app.handle_key(KeyCode::Enter);
+        assert!(!app.searching);
+        assert_eq!(app.filtered_len(), 1);
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_handle_key_actions() {
+        let mut app = app_with_commands(1);
+        assert_eq!(app.handle_key(KeyCode::Char('x')), None);
+        assert_eq!(app.pending_action, Some(PendingAction::Abort("0".to_string())));
+        assert_eq!(app.handle_key(KeyCode::Char('y')), Some(Action::Abort("0".to_string())));
+        assert!(app.pending_action.is_none());
+
+        app.confirm_quit = true;
+        assert_eq!(app.handle_key(KeyCode::Char('q')), None);
+        assert_eq!(app.handle_key(KeyCode::Char('n')), None);
+        assert_eq!(app.handle_key(KeyCode::Char('q')), None);
+        assert_eq!(app.handle_key(KeyCode::Char('q')), Some(Action::Quit));
+        app.confirm_quit = false;
+        assert_eq!(app.handle_key(KeyCode::Char('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn test_format_id() {
+        assert_eq!(format_id("123456789"), "123..789");
+        assert_eq!(format_id("123"), "123");
+        assert_eq!(format_id("123456"), "123456");
+    }
+
+    #[test]
+    fn test_full_id_width() {
+        assert_eq!(full_id_width(&[]), 4);
+        let a = command("abcdef0123456789", "cmd", 1_000, None);
+        let b = command("日本語", "cmd", 1_000, None);
+        assert_eq!(full_id_width(&[&a, &b]), 18);
+        assert_eq!(full_id_width(&[&b]), 5);
+    }
+
+    #[test]
+    fn test_details_path_stays_in_the_temp_dir() {
+        let path = details_path("../x");
+        assert_eq!(path.parent(), Some(std::env::temp_dir().as_path()));
+        assert_eq!(path.file_name().unwrap(), "anttpmon-___x.txt");
+        assert_eq!(details_path("ab-1_2").file_name().unwrap(), "anttpmon-ab-1_2.txt");
+        let path = details_path("/etc/passwd");
+        assert_eq!(path.file_name().unwrap(), "anttpmon-_etc_passwd.txt");
+    }
+
+    #[test]
+    fn test_format_id_multi_byte() {
+        assert_eq!(format_id("äöü123ßéè"), "äöü..ßéè");
+        assert_eq!(format_id("a€bcdef€"), "a€b..ef€");
+        assert_eq!(format_id("日本語日本語"), "日本語日本語");
+        assert_eq!(format_id("日本語のテキスト"), "日本語..キスト");
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.apply_config(&Config::default());
+        assert_eq!(app.refresh_interval, DEFAULT_REFRESH_INTERVAL);
+        assert_eq!(app.long_running_threshold_ms, DEFAULT_LONG_RUNNING_THRESHOLD_MS);
+        assert_eq!(app.state_filter, StateFilter::DEFAULT);
+
+        app.apply_config(&Config {
+            endpoint: None,
+            refresh_interval_ms: Some(1),
+            filter_mode: Some(FilterMode::Aborted),
+            long_running_threshold_ms: Some(5_000),
+            depth_history: Some(0),
+            max_commands: Some(500),
+            confirm_quit: Some(true),
+            columns: Some(vec![Column::Running, Column::Id]),
+            alert_on_abort: Some(AbortAlert::Bell),
+            theme: Some(ThemeName::HighContrast),
+            highlight_symbol: Some(String::new()),
+            selection_style: Some(SelectionStyle {
+                fg: Some("black".to_string()),
+                bg: Some("#ffcc00".to_string()),
+                modifiers: vec!["bold".to_string()],
+            }),
+            profiles: BTreeMap::from([("home".to_string(), profile("http://10.0.0.5:18887"))]),
+        });
+        assert_eq!(app.refresh_interval, MIN_REFRESH_INTERVAL);
+        assert_eq!(app.long_running_threshold_ms, 5_000);
+        // at least the latest refresh is kept
+        assert_eq!(app.depth_history_len, 1);
+        assert_eq!(app.max_commands, Some(500));
+        assert_eq!(app.state_filter, StateFilter::only(CommandState::Aborted));
+        assert!(app.confirm_quit);
+        assert_eq!(app.columns, [Column::Id, Column::Running]);
+        assert_eq!(app.alert_on_abort, Some(AbortAlert::Bell));
+        assert_eq!(app.theme, ThemeName::HighContrast);
+        // an empty symbol just indents the selected row
+        assert_eq!(app.highlight_symbol, "");
+        let style = Style::default().fg(Color::Black).bg(Color::Rgb(0xff, 0xcc, 0x00));
+        assert_eq!(app.theme().selection, style.add_modifier(Modifier::BOLD));
+        assert_eq!(app.profiles, [("home".to_string(), profile("http://10.0.0.5:18887"))]);
+        assert_eq!(app.status(), None);
+    }
+
+    #[test]
+    fn test_toggle_column() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.toggle_column(Column::State);
+        app.toggle_column(Column::Id);
+        assert_eq!(
+            app.columns,
+            [Column::Name, Column::Waiting, Column::Running, Column::Terminated]
+        );
+        // shown again in its usual place
+        app.toggle_column(Column::State);
+        assert_eq!(
+            app.columns,
+            [Column::Name, Column::State, Column::Waiting, Column::Running, Column::Terminated]
+        );
+        app.columns = vec![Column::Name];
+        app.toggle_column(Column::Name);
+        assert_eq!(app.columns, [Column::Name]);
+        assert_eq!(app.status(), Some("At least one column must be shown"));
+    }
+
+    #[test]
+    fn test_latency_history_keeps_the_latest() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        for ms in 0..LATENCY_HISTORY as u64 + 5 {
+            let latency = Some(Duration::from_millis(ms));
+            app.apply_refresh(FetchResult { client: None, result: Ok(vec![]), latency }).unwrap();
+        }
+        assert_eq!(app.latencies.len(), LATENCY_HISTORY);
+        assert_eq!(app.latencies.front(), Some(&Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_depth_history_keeps_the_latest() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.depth_history_len = 3;
+        let waiting = |count: usize| {
+            let mut commands: Vec<Command> =
+                (0..count).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect();
+            let mut running = command("running", "cmd", 1_000, Some(2_000));
+            running.state = "running".to_string();
+            commands.push(running);
+            commands
+        };
+        for count in [4, 5, 2, 0] {
+            let fetch = FetchResult { client: None, result: Ok(waiting(count)), latency: None };
+            app.apply_refresh(fetch).unwrap();
+        }
+        assert_eq!(app.depth_history, [5, 2, 0]);
+        // failed refreshes leave no sample
+        let result = Err(anyhow::anyhow!("down"));
+        assert!(app.apply_refresh(FetchResult { client: None, result, latency: None }).is_err());
+        assert_eq!(app.depth_history, [5, 2, 0]);
+    }
+
+    #[test]
+    fn test_invalid_endpoint_keeps_the_connection() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "cmd", 1_000, None)]);
+        app.last_refresh_ok = true;
+        app.endpoint_input = Some("not a url".to_string());
+        app.apply_endpoint_input();
+        assert_eq!(app.connection.endpoint, DEFAULT_ENDPOINT);
+        assert!(app.status().unwrap().starts_with("Invalid endpoint \"not a url\""));
+        assert!(app.last_refresh_ok);
+        assert_eq!(app.commands.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_switching_endpoint_reconnects() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "cmd", 1_000, None)]);
+        app.last_refresh_ok = true;
+        app.endpoint_input = Some(" http://127.0.0.1:1 ".to_string());
+        app.apply_endpoint_input();
+        assert_eq!(app.connection.endpoint, "http://127.0.0.1:1");
+        assert_eq!(app.status(), Some("Connecting to http://127.0.0.1:1..."));
+        assert!(app.fetching && !app.last_refresh_ok);
+        assert!(app.commands.is_empty());
+
+        let fetch = FetchResult {
+            client: None,
+            result: Err(tonic::Status::unavailable("refused").into()),
+            latency: None,
+        };
+        let _ = app.apply_refresh(fetch);
+        let failed = "Failed to connect to http://127.0.0.1:1: Unavailable: refused";
+        assert_eq!(app.status(), Some(failed));
+    }
+
+    fn profile(endpoint: &str) -> Profile {
+        Profile { endpoint: endpoint.to_string(), token: None, ca_cert: None }
+    }
+
+    #[test]
+    fn test_profile_picker_opens_on_active_profile() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.open_profiles();
+        assert!(!app.show_profiles);
+        assert_eq!(app.status(), Some("No profiles in the config file"));
+
+        app.profiles = vec![
+            ("home".to_string(), profile("http://10.0.0.5:18887")),
+            ("work".to_string(), profile("http://10.0.0.6:18887")),
+        ];
+        app.active_profile = Some("work".to_string());
+        app.open_profiles();
+        assert!(app.show_profiles);
+        assert_eq!(app.profiles_state.selected(), Some(1));
+
+        // an unusable profile keeps the current connection
+        app.profiles[1].1.endpoint = "nowhere".to_string();
+        app.apply_selected_profile();
+        assert!(!app.show_profiles);
+        assert_eq!(app.connection.endpoint, DEFAULT_ENDPOINT);
+        assert!(app.status().unwrap().starts_with("Profile work: invalid endpoint"));
+    }
+
+    #[tokio::test]
+    async fn test_switching_profile_reconnects() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.profiles = vec![("home".to_string(), profile("http://127.0.0.1:1"))];
+        app.open_profiles();
+        app.apply_selected_profile();
+        assert_eq!(app.connection.endpoint, "http://127.0.0.1:1");
+        assert_eq!(app.active_profile.as_deref(), Some("home"));
+        assert!(app.fetching);
+        let title: String = title(&app).spans.iter().map(|s| s.content.as_ref()).collect();
+        assert!(title.starts_with(" AntTP Monitor - home (http://127.0.0.1:1) "), "{title}");
+
+        // editing the endpoint by hand leaves the profile
+        app.endpoint_input = Some("http://127.0.0.1:2".to_string());
+        app.apply_endpoint_input();
+        assert_eq!(app.active_profile, None);
+    }
+
+    #[test]
+    fn test_refresh_interval_clamped() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.increase_refresh_interval();
+        assert_eq!(app.refresh_interval, Duration::from_millis(1250));
+        for _ in 0..100 {
+            app.increase_refresh_interval();
+        }
+        assert_eq!(app.refresh_interval, MAX_REFRESH_INTERVAL);
+        for _ in 0..100 {
+            app.decrease_refresh_interval();
+        }
+        assert_eq!(app.refresh_interval, MIN_REFRESH_INTERVAL);
+    }
+
+    #[test]
+    fn test_sort_by_waiting_is_numeric() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        // waiting for 10s and 9s respectively
+        app.set_commands(vec![
+            command("a", "ten", 1_000, Some(11_000)),
+            command("b", "nine", 1_000, Some(10_000)),
+        ]);
+        app.sort_column = Some(Column::Waiting);
+        let ids: Vec<&str> = app.filtered_commands().iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["b", "a"]);
+
+        app.sort_ascending = false;
+        let ids: Vec<&str> = app.filtered_commands().iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_compare_durations_missing_last() {
+        let mut values = vec![None, Some(10_000), None, Some(9_000), Some(20_000)];
+        values.sort_by(|a, b| compare_durations(*a, *b, true));
+        assert_eq!(values, [Some(9_000), Some(10_000), Some(20_000), None, None]);
+        values.sort_by(|a, b| compare_durations(*a, *b, false));
+        assert_eq!(values, [Some(20_000), Some(10_000), Some(9_000), None, None]);
+    }
+
+    #[test]
+    fn test_sort_by_running_with_missing_values() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut finished = command("c", "finished", 1_000, Some(2_000));
+        finished.terminated_at = Some(3_000);
+        app.set_commands(vec![
+            command("a", "queued", 1_000, None),
+            finished,
+            command("b", "queued", 1_000, None),
+        ]);
+        app.sort_column = Some(Column::Running);
+        for ascending in [true, false] {
+            app.sort_ascending = ascending;
+            let ids: Vec<&str> = app.filtered_commands().iter().map(|c| c.id.as_str()).collect();
+            assert_eq!(ids, ["c", "a", "b"]);
+        }
+    }
+
+    #[test]
+    fn test_search_filters_by_name_case_insensitively() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![
+            command("a", "UploadFile", 1_000, None),
+            command("b", "DownloadFile", 1_000, None),
+            command("c", "Upload Archive", 1_000, None),
+        ]);
+        app.search_query = "upload".to_string();
+        app.search_query_edited();
+        let ids: Vec<&str> = app.filtered_commands().iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["a", "c"]);
+    }
+
+    #[test]
+    fn test_regex_search_matches_names() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![
+            command("a", "backup-photos-2024", 1_000, None),
+            command("b", "backup-photos-2023", 1_000, None),
+            command("c", "restore-backup-2024", 1_000, None),
+        ]);
+        app.search_query = "^backup-.*-2024$".to_string();
+        app.toggle_regex_search();
+        let ids: Vec<&str> = app.filtered_commands().iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["a"]);
+
+        // the same query as a substring matches nothing
+        app.toggle_regex_search();
+        assert!(app.filtered_commands().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_regex_search_does_not_filter() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "upload", 1_000, None)]);
+        app.toggle_regex_search();
+        app.search_query = "(upload".to_string();
+        app.search_query_edited();
+        assert!(matches!(&app.search_regex, Some(Err(e)) if e == "unclosed group"));
+        assert_eq!(app.filtered_len(), 1);
+
+        app.search_query.push(')');
+        app.search_query_edited();
+        assert!(matches!(app.search_regex, Some(Ok(_))));
+        assert_eq!(app.filtered_len(), 1);
+    }
+
+    #[test]
+    fn test_command_matches_query() {
+        let mut cmd = command("abcdef0123456789", "UploadFile", 1_000, None);
+        cmd.properties.push(Property {
+            name: "address".to_string(),
+            value: "https://example.com/Archive".to_string(),
+        });
+        assert!(command_matches_query(&cmd, ""));
+        assert!(command_matches_query(&cmd, "upload"));
+        // matches the middle of the full ID, which the shortened form hides
+        assert!(command_matches_query(&cmd, "f0123"));
+        assert!(command_matches_query(&cmd, "ADDRESS"));
+        assert!(command_matches_query(&cmd, "archive"));
+        assert!(!command_matches_query(&cmd, "download"));
+    }
+
+    #[test]
+    fn test_restore_selection_follows_id() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![
+            command("a", "first", 1_000, None),
+            command("b", "second", 1_000, None),
+        ]);
+        app.table_state.select(Some(1));
+        let selected_id = app.selected_id();
+        assert_eq!(selected_id.as_deref(), Some("b"));
+
+        // list reorders and grows
+        app.set_commands(vec![
+            command("c", "third", 1_000, None),
+            command("b", "second", 1_000, None),
+            command("a", "first", 1_000, None),
+        ]);
+        app.restore_selection(selected_id);
+        assert_eq!(app.selected_id().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_apply_refresh() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.fetching = true;
+        let fetch = FetchResult {
+            client: None,
+            result: Ok(vec![command("a", "first", 1_000, None)]),
+            latency: Some(Duration::from_millis(12)),
+        };
+        assert!(app.apply_refresh(fetch).is_ok());
+        assert!(!app.fetching);
+        assert!(app.last_refresh_ok);
+        assert!(app.last_successful_refresh.is_some());
+        assert_eq!(app.commands.len(), 1);
+        assert_eq!(app.latencies, [Duration::from_millis(12)]);
+
+        let fetch = FetchResult {
+            client: None,
+            result: Err(tonic::Status::unavailable("down").into()),
+            latency: None,
+        };
+        assert!(app.apply_refresh(fetch).is_err());
+        assert!(!app.last_refresh_ok);
+        assert_eq!(app.last_error.as_deref(), Some("Unavailable: down"));
+        assert_eq!(app.error_log.len(), 1);
+        // the last good data is kept
+        assert_eq!(app.commands.len(), 1);
+    }
+
+    #[test]
+    fn test_restore_selection_falls_back_to_nearest() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![
+            command("a", "first", 1_000, None),
+            command("b", "second", 1_000, None),
+            command("c", "third", 1_000, None),
+        ]);
+        app.table_state.select(Some(2));
+        let selected_id = app.selected_id();
+
+        app.set_commands(app.commands[..2].to_vec());
+        app.restore_selection(selected_id.clone());
+        assert_eq!(app.table_state.selected(), Some(1));
+
+        app.set_commands(Vec::new());
+        app.restore_selection(selected_id);
+        assert_eq!(app.table_state.selected(), None);
+    }
+
+    #[test]
+    fn test_paging_stops_at_boundaries() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands((0..10).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect());
+        app.page_size = 4;
+        app.page_down();
+        assert_eq!(app.table_state.selected(), Some(4));
+        app.page_down();
+        app.page_down();
+        assert_eq!(app.table_state.selected(), Some(9));
+        app.page_up();
+        assert_eq!(app.table_state.selected(), Some(5));
+        app.first();
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.page_up();
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.last();
+        assert_eq!(app.table_state.selected(), Some(9));
+    }
+
+    #[test]
+    fn test_gg_jumps_to_first_row() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands((0..10).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect());
+        app.table_state.select(Some(5));
+        app.press_g(None);
+        assert_eq!(app.table_state.selected(), Some(5));
+        // a second g, right after the first
+        let pending = app.pending_g.take();
+        app.press_g(pending);
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        app.table_state.select(Some(5));
+        let stale = Instant::now().checked_sub(KEY_SEQUENCE_TIMEOUT);
+        app.press_g(stale);
+        assert_eq!(app.table_state.selected(), Some(5));
+        assert!(app.pending_g.is_some());
+    }
+
+    #[test]
+    fn test_follow_selects_newest_until_navigating() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "cmd", 2_000, None), command("b", "cmd", 1_000, None)]);
+        app.table_state.select(Some(1));
+        app.toggle_follow();
+        assert_eq!(app.selected_id().as_deref(), Some("a"));
+
+        let newer = vec![
+            command("a", "cmd", 2_000, None),
+            command("b", "cmd", 1_000, None),
+            command("c", "cmd", 3_000, None),
+        ];
+        app.replace_commands(newer.clone());
+        assert_eq!(app.selected_id().as_deref(), Some("c"));
+
+        // moving by hand stops following, so the next refresh keeps the selection
+        app.previous();
+        assert!(!app.follow);
+        let mut newest = newer;
+        newest.push(command("d", "cmd", 4_000, None));
+        app.replace_commands(newest);
+        assert_eq!(app.selected_id().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_row_at_maps_clicks_to_rows() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands((0..3).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect());
+        app.table_area = Rect::new(0, 0, 80, 20);
+        // border, header and header margin
+        assert_eq!(app.row_at(10, 0), None);
+        assert_eq!(app.row_at(10, 2), None);
+        assert_eq!(app.row_at(10, 3), Some(0));
+        assert_eq!(app.row_at(10, 5), Some(2));
+        // below the last command
+        assert_eq!(app.row_at(10, 6), None);
+        // side borders
+        assert_eq!(app.row_at(0, 3), None);
+        assert_eq!(app.row_at(79, 3), None);
+    }
+
+    #[test]
+    fn test_click_selects_and_double_click_opens() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands((0..3).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect());
+        app.table_area = Rect::new(0, 0, 80, 20);
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 10,
+            row: 4,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        };
+        app.handle_mouse(click);
+        assert_eq!(app.table_state.selected(), Some(1));
+        assert!(app.selected_command.is_none());
+        app.handle_mouse(click);
+        assert_eq!(app.selected_command.map(|c| c.id), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_property_cursor() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut cmd = command("a", "cmd", 1_000, None);
+        cmd.properties = ["url", "hash", "size"]
+            .iter()
+            .map(|name| Property { name: name.to_string(), value: format!("{name}!") })
+            .collect();
+        app.set_commands(vec![cmd, command("b", "cmd", 1_000, None)]);
+        app.table_state.select(Some(0));
+        app.open_selected();
+        app.move_property_cursor(-1);
+        assert_eq!(app.selected_property, 0);
+        app.move_property_cursor(1);
+        app.move_property_cursor(5);
+        assert_eq!(app.selected_property, 2);
+        // each command starts at its first property, even with none
+        app.open_adjacent(1);
+        assert_eq!(app.selected_property, 0);
+        app.move_property_cursor(1);
+        assert_eq!(app.selected_property, 0);
+    }
+
+    #[test]
+    fn test_open_adjacent_moves_details_through_view() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands((0..3).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect());
+        app.table_state.select(Some(0));
+        app.open_selected();
+        app.detail_scroll = 4;
+        app.open_adjacent(1);
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("1"));
+        assert_eq!(app.detail_scroll, 0);
+        app.open_adjacent(5);
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("2"));
+        app.open_adjacent(-1);
+        assert_eq!(app.table_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_resize_column_follows_sort_column() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.resize_column(4);
+        assert_eq!(app.column_width(Column::Name), 24);
+        assert_eq!(app.status(), Some("Name column width: 24"));
+        app.sort_column = Some(Column::Waiting);
+        app.resize_column(-20);
+        assert_eq!(app.column_width(Column::Waiting), MIN_COLUMN_WIDTH);
+        assert_eq!(app.column_width(Column::Running), 12);
+    }
+
+    #[test]
+    fn test_state_restores_sort_and_widths() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.state_filter = StateFilter::ALL;
+        app.sort_column = Some(Column::Running);
+        app.sort_ascending = false;
+        app.column_widths.insert(Column::Name, 30);
+        let mut state = app.state();
+
+        // widths outside the allowed range fall back to the default
+        state.column_widths.insert(Column::State, 0);
+        state.column_widths.insert(Column::Id, MAX_COLUMN_WIDTH + 1);
+        let mut restored = App::new(DEFAULT_ENDPOINT.to_string());
+        restored.apply_state(state);
+        assert_eq!(restored.state(), app.state());
+        assert_eq!(restored.column_width(Column::State), 12);
+        assert_eq!(restored.column_width(Column::Id), 10);
+
+        restored.apply_state(State::default());
+        assert_eq!(restored.state(), App::new(DEFAULT_ENDPOINT.to_string()).state());
+    }
+
+    #[test]
+    fn test_request_abort() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.state_filter = StateFilter::ALL;
+        let mut finished = command("2", "b", 1_000, Some(2_000));
+        finished.terminated_at = Some(3_000);
+        app.set_commands(vec![command("1", "a", 1_000, None), finished]);
+        app.request_abort();
+        assert_eq!(app.pending_action, None);
+        app.table_state.select(Some(0));
+        app.request_abort();
+        assert_eq!(app.pending_action, Some(PendingAction::Abort("1".to_string())));
+
+        app.pending_action = None;
+        app.table_state.select(Some(1));
+        app.request_abort();
+        assert_eq!(app.pending_action, None);
+        assert!(app.status().is_some_and(|s| s.contains("already finished")));
+    }
+
+    #[test]
+    fn test_error_log_is_bounded() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        for i in 0..ERROR_LOG_CAPACITY + 5 {
+            app.log_error(i.to_string());
+        }
+        assert_eq!(app.error_log.len(), ERROR_LOG_CAPACITY);
+        assert_eq!(app.error_log.front().map(|(_, m)| m.as_str()), Some("5"));
+    }
+
+    #[test]
+    fn test_log_file_rotates() {
+        let dir = std::env::temp_dir().join(format!("anttpmon-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_file = LogFile { path: dir.join("anttpmon.log"), max_bytes: 40 };
+        log_file.write("first");
+        log_file.write("second");
+        let log = std::fs::read_to_string(&log_file.path).unwrap();
+        assert!(log.lines().count() == 2 && log.ends_with(" second\n"));
+        log_file.write("third");
+        let rotated = std::fs::read_to_string(dir.join("anttpmon.log.1")).unwrap();
+        assert_eq!(rotated, log);
+        let log = std::fs::read_to_string(&log_file.path).unwrap();
+        assert!(log.lines().count() == 1 && log.ends_with(" third\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn grouped_app() -> App {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![
+            command("a", "upload", 1_000, None),
+            command("b", "download", 2_000, None),
+            command("c", "upload", 3_000, None),
+        ]);
+        app.group_by_name = true;
+        app
+    }
+
+    #[test]
+    fn test_group_by_name() {
+        let mut app = grouped_app();
+        let group = |name: &str, count, collapsed| ViewRow::Group {
+            name: name.to_string(),
+            count,
+            collapsed,
+        };
+        assert_eq!(
+            &*app.rows(),
+            &[
+                group("upload", 2, false),
+                ViewRow::Command(0),
+                ViewRow::Command(2),
+                group("download", 1, false),
+                ViewRow::Command(1),
+            ]
+        );
+
+        // Enter on a header collapses it, leaving the header selected
+        app.table_state.select(Some(0));
+        app.open_selected();
+        assert!(app.selected_command.is_none());
+        assert_eq!(
+            &*app.rows(),
+            &[group("upload", 2, true), group("download", 1, false), ViewRow::Command(1)]
+        );
+        app.next();
+        assert_eq!(app.selected_group().as_deref(), Some("download"));
+        app.next();
+        assert_eq!(app.selected_id().as_deref(), Some("b"));
+        assert_eq!(app.filtered_len(), 3);
+    }
+
+    #[test]
+    fn test_group_selection_survives_refresh_and_toggle() {
+        let mut app = grouped_app();
+        app.table_state.select(Some(3));
+        let commands = app.commands.to_vec();
+        let fetch = FetchResult { client: None, result: Ok(commands.clone()), latency: None };
+        app.apply_refresh(fetch).unwrap();
+        assert_eq!(app.selected_group().as_deref(), Some("download"));
+
+        app.table_state.select(Some(2));
+        app.toggle_grouping();
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.selected_id().as_deref(), Some("c"));
+        app.toggle_grouping();
+        assert_eq!(app.selected_id().as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_open_adjacent_skips_group_headers() {
+        let mut app = grouped_app();
+        app.table_state.select(Some(2));
+        app.open_selected();
+        app.open_adjacent(1);
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("b"));
+        app.open_adjacent(-1);
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("c"));
+        app.table_state.select(Some(1));
+        app.open_adjacent(-1);
+        assert_eq!(app.table_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_jump_to_aborted_wraps() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.state_filter = StateFilter::ALL;
+        let mut commands: Vec<Command> =
+            (0..5).map(|i| command(&i.to_string(), "cmd", 1_000, None)).collect();
+        commands[1].state = "aborted".to_string();
+        commands[3].state = "Aborted".to_string();
+        app.set_commands(commands);
+
+        app.jump_to_aborted(true);
+        assert_eq!(app.table_state.selected(), Some(1));
+        app.jump_to_aborted(true);
+        assert_eq!(app.table_state.selected(), Some(3));
+        app.jump_to_aborted(true);
+        assert_eq!(app.table_state.selected(), Some(1));
+        app.jump_to_aborted(false);
+        assert_eq!(app.table_state.selected(), Some(3));
+
+        app.state_filter = StateFilter::only(CommandState::Waiting);
+        app.table_state.select(Some(2));
+        app.jump_to_aborted(true);
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.status(), Some("No aborted commands"));
+    }
+
+    #[test]
+    fn test_state_filter_toggles_combine() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let states = ["waiting", "running", "completed", "aborted"];
+        let commands = states
+            .iter()
+            .map(|state| {
+                let mut cmd = command(state, "cmd", 1_000, None);
+                cmd.state = state.to_string();
+                cmd
+            })
+            .collect();
+        app.set_commands(commands);
+        let ids = |app: &App| -> Vec<String> {
+            app.filtered_commands().iter().map(|c| c.id.clone()).collect()
+        };
+        assert_eq!(ids(&app), ["waiting", "running"]);
+
+        app.toggle_state_filter(CommandState::Running);
+        app.toggle_state_filter(CommandState::Aborted);
+        assert_eq!(ids(&app), ["waiting", "aborted"]);
+        assert_eq!(app.state_filter.label(), "Waiting + Aborted");
+
+        app.set_state_filter(StateFilter::ALL);
+        assert_eq!(app.state_filter.label(), "All");
+        app.toggle_state_filter(CommandState::Waiting);
+        assert_eq!(ids(&app), ["running", "completed", "aborted"]);
+        app.set_state_filter(StateFilter::DEFAULT);
+        assert_eq!(app.state_filter.label(), "Default (W/R)");
+        app.toggle_state_filter(CommandState::Waiting);
+        app.toggle_state_filter(CommandState::Running);
+        assert_eq!(app.state_filter.label(), "None");
+        assert!(ids(&app).is_empty());
+    }
+
+    #[test]
+    fn test_state_filter_round_trips_through_states() {
+        let filter = StateFilter::from(vec![CommandState::Aborted, CommandState::Waiting]);
+        assert_eq!(Vec::from(filter), [CommandState::Waiting, CommandState::Aborted]);
+        assert_eq!(StateFilter::from(Vec::from(StateFilter::ALL)), StateFilter::ALL);
+    }
+
+    #[test]
+    fn test_freeze_keeps_snapshot_until_unfrozen() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "cmd", 1_000, None)]);
+        app.table_state.select(Some(0));
+        app.toggle_freeze();
+        let frozen_ms = app.now_ms();
+        assert!(!app.has_running_timers());
+
+        let live = vec![command("b", "cmd", 500, None), command("a", "cmd", 1_000, None)];
+        app.apply_refresh(FetchResult { client: None, result: Ok(live), latency: None }).unwrap();
+        assert!(app.last_refresh_ok);
+        assert_eq!(app.commands.len(), 1);
+        assert_eq!(app.now_ms(), frozen_ms);
+
+        app.toggle_freeze();
+        assert_eq!(app.commands.len(), 2);
+        assert_eq!(app.selected_id().as_deref(), Some("a"));
+        assert!(app.live_commands.is_none());
+    }
+
+    #[test]
+    fn test_replay_shows_snapshot_frozen() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let snapshot = Snapshot {
+            captured_at: 10_000,
+            endpoint: "http://10.0.0.5:18887".to_string(),
+            commands: vec![command("a", "cmd", 1_000, Some(4_000))],
+        };
+        app.start_replay("snapshot.json".into(), snapshot);
+        assert_eq!(app.now_ms(), 10_000);
+        assert_eq!(app.connection.endpoint, "http://10.0.0.5:18887");
+        assert_eq!(app.selected_id().as_deref(), Some("a"));
+
+        app.refresh_commands();
+        assert!(!app.fetching);
+        app.toggle_freeze();
+        assert_eq!(app.now_ms(), 10_000);
+    }
+
+    #[test]
+    fn test_refresh_marks_new_and_changed_commands() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let fetch = |commands| FetchResult { client: None, result: Ok(commands), latency: None };
+        let waiting = command("a", "cmd", 1_000, None);
+        app.apply_refresh(fetch(vec![waiting.clone(), command("b", "cmd", 1_000, None)])).unwrap();
+        // nothing to compare the first commands with
+        assert!(app.changes.is_empty());
+
+        let mut running = waiting.clone();
+        running.state = "running".to_string();
+        let next =
+            vec![running, command("b", "cmd", 1_000, None), command("c", "cmd", 1_000, None)];
+        app.apply_refresh(fetch(next.clone())).unwrap();
+        assert_eq!(app.changes.len(), 2);
+        assert_eq!(app.changes.get("a"), Some(&DiffKind::Changed));
+        assert_eq!(app.changes.get("c"), Some(&DiffKind::Added));
+
+        // the highlight lasts one refresh
+        app.apply_refresh(fetch(next)).unwrap();
+        assert!(app.changes.is_empty());
+    }
+
+    #[test]
+    fn test_alert_only_on_new_aborts() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.alert_on_abort = Some(AbortAlert::Both);
+        let fetch = |commands| FetchResult { client: None, result: Ok(commands), latency: None };
+        let mut aborted = command("a", "cmd", 1_000, None);
+        aborted.state = "aborted".to_string();
+        let waiting = command("b", "cmd", 1_000, None);
+        app.apply_refresh(fetch(vec![aborted.clone(), waiting.clone()])).unwrap();
+        // already aborted when first fetched
+        assert!(!app.ring_bell);
+        assert!(app.abort_flash.is_none());
+
+        let mut now_aborted = waiting.clone();
+        now_aborted.state = "Aborted".to_string();
+        app.apply_refresh(fetch(vec![aborted.clone(), now_aborted.clone()])).unwrap();
+        assert!(app.ring_bell);
+        assert!(app.abort_flash.is_some());
+        assert_eq!(app.status(), Some("Command b aborted"));
+
+        app.ring_bell = false;
+        app.apply_refresh(fetch(vec![aborted, now_aborted])).unwrap();
+        assert!(!app.ring_bell);
+    }
+
+    #[test]
+    fn test_command_store_merge() {
+        let mut store = CommandStore::from(vec![
+            command("a", "cmd", 1_000, None),
+            command("b", "cmd", 1_000, None),
+            command("c", "cmd", 1_000, None),
+        ]);
+        let mut running = command("b", "cmd", 1_000, Some(2_000));
+        running.state = "running".to_string();
+        let changes = store.merge(vec![
+            command("d", "cmd", 1_000, None),
+            running,
+            command("a", "cmd", 1_000, None),
+        ]);
+        assert_eq!(
+            changes,
+            StoreChanges {
+                added: vec!["d".to_string()],
+                removed: vec!["c".to_string()],
+                state_changed: vec!["b".to_string()],
+            }
+        );
+        // in the order fetched, and found by id
+        let ids: Vec<&str> = store.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["d", "b", "a"]);
+        assert_eq!(store.get("b").map(|c| c.state.as_str()), Some("running"));
+        assert!(store.get("c").is_none());
+        assert_eq!(store.merge(store.to_vec()), StoreChanges::default());
+    }
+
+    #[test]
+    fn test_retain_recent() {
+        let mut commands: Vec<Command> =
+            [5_000, 1_000, 9_000, 3_000].map(|ms| command(&ms.to_string(), "cmd", ms, None)).into();
+        retain_recent(&mut commands, 4);
+        assert_eq!(commands.len(), 4);
+        // the latest activity is kept, in the order given
+        retain_recent(&mut commands, 2);
+        let ids: Vec<&str> = commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["5000", "9000"]);
+    }
+
+    #[test]
+    fn test_max_commands_caps_refreshes() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.max_commands = Some(2);
+        let fetched = (1..=4).map(|i| command(&i.to_string(), "cmd", i * 1_000, None)).collect();
+        let fetch = FetchResult { client: None, result: Ok(fetched), latency: None };
+        app.apply_refresh(fetch).unwrap();
+        let ids: Vec<&str> = app.commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["3", "4"]);
+        assert_eq!(app.reported_commands, 4);
+        let summary = retained_summary(app.commands.len(), app.reported_commands);
+        assert_eq!(summary, "showing 2 of 4 | ");
+
+        // a newer command pushes out the oldest kept
+        let fetched = (1..=5).map(|i| command(&i.to_string(), "cmd", i * 1_000, None)).collect();
+        let fetch = FetchResult { client: None, result: Ok(fetched), latency: None };
+        app.apply_refresh(fetch).unwrap();
+        let ids: Vec<&str> = app.commands.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["4", "5"]);
+        assert_eq!(app.reported_commands, 5);
+
+        // nothing is said while everything is kept
+        assert_eq!(retained_summary(3, 3), "");
+    }
+
+    #[test]
+    fn test_refresh_updates_open_details() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let fetch = |commands| FetchResult { client: None, result: Ok(commands), latency: None };
+        app.apply_refresh(fetch(vec![command("a", "cmd", 1_000, None)])).unwrap();
+        app.table_state.select(Some(0));
+        app.open_selected();
+
+        let mut running = command("a", "cmd", 1_000, Some(2_000));
+        running.state = "running".to_string();
+        app.apply_refresh(fetch(vec![running])).unwrap();
+        assert_eq!(app.selected_command.as_ref().map(|c| c.state.as_str()), Some("running"));
+
+        app.apply_refresh(fetch(Vec::new())).unwrap();
+        assert_eq!(app.selected_command.as_ref().map(|c| c.id.as_str()), Some("a"));
+        assert_eq!(app.status(), Some("Command a is no longer reported by the server"));
+    }
+
+    #[test]
+    fn test_needs_redraw() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        assert!(app.needs_redraw());
+        app.dirty = false;
+        assert!(!app.needs_redraw());
+
+        let mut completed = command("1", "a", 1_000, Some(2_000));
+        completed.state = "completed".to_string();
+        completed.terminated_at = Some(3_000);
+        app.set_commands(vec![completed]);
+        assert!(!app.needs_redraw());
+        app.set_commands(vec![command("2", "b", 1_000, Some(2_000))]);
+        assert!(app.needs_redraw());
+
+        app.set_commands(Vec::new());
+        app.status = Some(("Copied".to_string(), Instant::now() - STATUS_DURATION));
+        assert!(app.needs_redraw());
+        assert!(app.status.is_none());
+
+        // the spinner turns while a refresh is in flight
+        app.dirty = false;
+        app.fetching = true;
+        assert!(app.needs_redraw());
+    }
+
+    #[test]
+    fn test_request_quit() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        assert!(app.request_quit());
+        assert_eq!(app.pending_action, None);
+        app.confirm_quit = true;
+        assert!(!app.request_quit());
+        assert_eq!(app.pending_action, Some(PendingAction::Quit));
+    }
+
+    #[test]
+    fn test_view_is_invalidated_by_changes() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(vec![command("a", "up", 1_000, None), command("b", "down", 2_000, None)]);
+        assert_eq!(app.filtered_len(), 2);
+        app.search_query = "up".to_string();
+        app.search_query_edited();
+        assert_eq!(app.filtered_len(), 1);
+        app.search_query.clear();
+        app.search_query_edited();
+        app.state_filter = StateFilter::only(CommandState::Completed);
+        assert_eq!(app.filtered_len(), 0);
+        app.state_filter = StateFilter::ALL;
+        app.sort_column = Some(Column::Name);
+        assert_eq!(app.row_command(0).map(|c| c.id.as_str()), Some("b"));
+        app.sort_ascending = false;
+        assert_eq!(app.row_command(0).map(|c| c.id.as_str()), Some("a"));
+        app.set_commands(vec![command("c", "z", 1_000, None)]);
+        assert_eq!(app.row_command(0).map(|c| c.id.as_str()), Some("c"));
+    }
+
+    #[test]
+    fn test_sort_column_cycle() {
+        let mut column = None;
+        for _ in 0..6 {
+            column = Column::next(column);
+            assert!(column.is_some());
+        }
+        assert_eq!(Column::next(column), None);
+    }
+
+    #[test]
+    fn test_age_filter() {
+        assert_eq!(AgeFilter::parse("<10"), Some(AgeFilter::YoungerThan(10)));
+        assert_eq!(AgeFilter::parse(" > 300s"), Some(AgeFilter::OlderThan(300)));
+        let older = AgeFilter::OlderThan(5);
+        assert_eq!(AgeFilter::parse(&older.label()), Some(older));
+        assert_eq!(AgeFilter::parse("10"), None);
+        assert_eq!(AgeFilter::parse("<ten"), None);
+
+        // waiting since 1s, running since 5s and completed at 9s
+        let waiting = command("1", "a", 1_000, None);
+        let mut running = command("2", "a", 1_000, Some(5_000));
+        running.state = "running".to_string();
+        let mut completed = command("3", "a", 1_000, Some(5_000));
+        completed.state = "completed".to_string();
+        completed.terminated_at = Some(9_000);
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.state_filter = StateFilter::ALL;
+        app.set_commands(vec![waiting, running, completed]);
+        app.frozen_at = Utc.timestamp_millis_opt(10_000).single();
+
+        let ids = |app: &App| -> Vec<String> {
+            app.filtered_commands().iter().map(|c| c.id.clone()).collect()
+        };
+        app.age_input = Some("<6".to_string());
+        app.apply_age_input();
+        assert_eq!(ids(&app), ["2", "3"]);
+        app.age_filter = Some(AgeFilter::OlderThan(6));
+        assert_eq!(ids(&app), ["1"]);
+        // combined with the state filter
+        app.state_filter = StateFilter::only(CommandState::Completed);
+        app.age_filter = Some(AgeFilter::YoungerThan(6));
+        assert_eq!(ids(&app), ["3"]);
+
+        app.age_input = Some("soon".to_string());
+        app.apply_age_input();
+        assert_eq!(app.age_filter, Some(AgeFilter::YoungerThan(6)));
+        app.age_input = Some(String::new());
+        app.apply_age_input();
+        assert_eq!(app.age_filter, None);
+    }
+
+    #[test]
+    fn test_command_state_parse() {
+        assert_eq!(CommandState::parse("waiting"), CommandState::Waiting);
+        assert_eq!(CommandState::parse("RUNNING"), CommandState::Running);
+        assert_eq!(CommandState::parse("Completed"), CommandState::Completed);
+        assert_eq!(CommandState::parse("aBoRtEd"), CommandState::Aborted);
+        assert_eq!(CommandState::parse("paused"), CommandState::Unknown);
+        assert_eq!(CommandState::parse(""), CommandState::Unknown);
+        assert_eq!(CommandState::parse(" running"), CommandState::Unknown);
+    }
+
+    #[test]
+    fn test_unknown_state_only_in_all_filter() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut paused = command("1", "a", 1_000, None);
+        paused.state = "paused".to_string();
+        app.set_commands(vec![paused]);
+        for mode in [
+            FilterMode::Default,
+            FilterMode::Waiting,
+            FilterMode::Running,
+            FilterMode::Completed,
+            FilterMode::Aborted,
+        ] {
+            app.state_filter = mode.states();
+            assert!(app.filtered_commands().is_empty());
+        }
+        app.state_filter = FilterMode::All.states();
+        assert_eq!(app.filtered_len(), 1);
+    }
+
+    #[test]
+    fn test_long_running_threshold_steps() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.increase_long_running_threshold();
+        assert_eq!(app.long_running_threshold_ms, 65_000);
+        for _ in 0..20 {
+            app.decrease_long_running_threshold();
+        }
+        assert_eq!(app.long_running_threshold_ms, LONG_RUNNING_THRESHOLD_STEP_MS);
+    }
+
+    #[test]
+    fn test_inverted_timestamps_are_marked() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut cmd = command("a", "cmd", 2_000, Some(1_000));
+        let times = app.time_cells(&cmd, 10_000);
+        assert_eq!((times.waiting.as_str(), times.running.as_str()), ("0.000?", "9.000"));
+        assert_eq!(times.warning().as_deref(), Some("Warning: running_at is before waiting_at"));
+
+        cmd.running_at = Some(3_000);
+        cmd.terminated_at = Some(2_500);
+        let times = app.time_cells(&cmd, 10_000);
+        assert_eq!((times.waiting.as_str(), times.running.as_str()), ("1.000", "0.000?"));
+        let warning = "\nWarning: terminated_at is before running_at\n";
+        assert!(app.details_text(&cmd, 10_000).contains(warning));
+
+        // finishing before it was queued doesn't touch how long it waited
+        cmd.terminated_at = Some(1_500);
+        let times = app.time_cells(&cmd, 10_000);
+        assert_eq!((times.waiting.as_str(), times.running.as_str()), ("1.000", "0.000?"));
+
+        // nothing is clamped when showing the timestamps themselves
+        app.absolute_times = true;
+        let times = app.time_cells(&cmd, 10_000);
+        assert!(times.warning().is_some());
+        let cells = [times.waiting, times.running];
+        assert!(cells.iter().all(|cell| !cell.contains('?')), "{cells:?}");
+        app.absolute_times = false;
+
+        cmd.terminated_at = Some(4_000);
+        assert_eq!(app.time_cells(&cmd, 10_000).warning(), None);
+    }
+
+    #[test]
+    fn test_details_text() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut cmd = command("abc", "upload", 1_000, Some(3_000));
+        cmd.state = "running".to_string();
+        cmd.properties = vec![
+            Property { name: "path".to_string(), value: "/a".to_string() },
+            Property { name: "meta".to_string(), value: r#"{"k":1}"#.to_string() },
+        ];
+        let text = app.details_text(&cmd, 10_000);
+        let expected = [
+            "ID: abc",
+            "Name: upload",
+            "State: running",
+            "Waiting: 2.000 s",
+            "Running: 7.000 s",
+            "Completed/Aborted: - s",
+        ];
+        assert!(text.starts_with(&(expected.join("\n") + "\n")), "{text}");
+        assert!(text.contains("\nRunning at: 3000 ("), "{text}");
+        let properties = "Properties:\n  path: /a\n  meta: {\n    \"k\": 1\n  }\n";
+        assert!(text.ends_with(properties), "{text}");
+
+        app.absolute_times = true;
+        assert!(!app.details_text(&cmd, 10_000).contains(" s\n"));
+    }
+}
@@ -0,0 +1,210 @@
+//! The config file, and what is remembered between runs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use tonic::transport::Certificate;
+
+use crate::app::{App, Column, FilterMode, StateFilter};
+use crate::grpc::{Connection, validate_endpoint};
+use crate::snapshot::write_json;
+use crate::ui::{SelectionStyle, ThemeName};
+
+/// Defaults read from the config file, each overriding the built-in default when set.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub endpoint: Option<String>,
+    pub refresh_interval_ms: Option<u64>,
+    pub filter_mode: Option<FilterMode>,
+    pub long_running_threshold_ms: Option<u64>,
+    /// How many refreshes the queue depth trend in the statistics covers.
+    pub depth_history: Option<usize>,
+    /// How many commands to keep, dropping those with the oldest activity. All by default.
+    pub max_commands: Option<usize>,
+    /// Ask before quitting, in case `q` was pressed by accident.
+    pub confirm_quit: Option<bool>,
+    /// The table columns to show, always in their usual order.
+    pub columns: Option<Vec<Column>>,
+    pub alert_on_abort: Option<AbortAlert>,
+    pub theme: Option<ThemeName>,
+    /// Marks the selected row, `">> "` by default. May be empty.
+    pub highlight_symbol: Option<String>,
+    /// Overrides the theme's selection style.
+    pub selection_style: Option<SelectionStyle>,
+    /// Named connections to switch between with `U`.
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named connection to an AntTP instance, from the config file.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub endpoint: String,
+    pub token: Option<String>,
+    /// PEM file with a CA certificate to trust for an https endpoint.
+    pub ca_cert: Option<std::path::PathBuf>,
+}
+
+impl Profile {
+    /// The connection the profile describes, with the given request timeout.
+    pub fn connection(&self, request_timeout: Duration) -> Result<Connection> {
+        validate_endpoint(&self.endpoint)
+            .with_context(|| format!("invalid endpoint {:?}", self.endpoint))?;
+        let mut connection = Connection::new(self.endpoint.clone());
+        connection.request_timeout = request_timeout;
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+            connection.ca_cert = Some(Certificate::from_pem(pem));
+        }
+        if let Some(token) = self.token.as_deref().filter(|t| !t.is_empty()) {
+            connection.set_token(token)?;
+        }
+        Ok(connection)
+    }
+}
+
+/// How to alert that a command has aborted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbortAlert {
+    Bell,
+    Flash,
+    Both,
+}
+
+impl AbortAlert {
+    pub fn bell(self) -> bool {
+        matches!(self, AbortAlert::Bell | AbortAlert::Both)
+    }
+
+    pub fn flash(self) -> bool {
+        matches!(self, AbortAlert::Flash | AbortAlert::Both)
+    }
+}
+
+pub fn config_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("anttpmon").join("config.toml"))
+}
+
+/// Loads the config file. A missing file is only an error if it was asked for explicitly.
+pub fn load_config(path: &std::path::Path, explicit: bool) -> Result<Config> {
+    match std::fs::read_to_string(path) {
+        Ok(config) => toml::from_str(&config)
+            .with_context(|| format!("invalid config file {}", path.display())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !explicit => Ok(Config::default()),
+        Err(err) => {
+            Err(err).with_context(|| format!("failed to read config file {}", path.display()))
+        }
+    }
+}
+
+/// What is remembered between runs.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub state_filter: StateFilter,
+    pub sort_column: Option<Column>,
+    pub sort_ascending: bool,
+    pub column_widths: HashMap<Column, u16>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            state_filter: StateFilter::default(),
+            sort_column: None,
+            sort_ascending: true,
+            column_widths: HashMap::new(),
+        }
+    }
+}
+
+pub fn state_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("anttpmon").join("state.json"))
+}
+
+/// Loads the saved state, or `None` if it is missing or unreadable, as if nothing was saved.
+pub fn load_state(path: &std::path::Path) -> Option<State> {
+    std::fs::read_to_string(path).ok().and_then(|json| serde_json::from_str(&json).ok())
+}
+
+/// Sets a pane up from the config file, then from what was remembered from the last run, so
+/// the config file only stands in for the built-in defaults.
+pub fn apply_settings(app: &mut App, config: &Config, state: Option<&State>) {
+    app.apply_config(config);
+    if let Some(state) = state {
+        app.apply_state(state.clone());
+    }
+}
+
+pub fn save_state(path: &std::path::Path, state: &State) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_json(path, state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_ENDPOINT;
+    use crate::app::CommandState;
+
+    #[test]
+    fn test_load_config_missing_file() {
+        let path = std::env::temp_dir().join("anttpmon-missing").join("config.toml");
+        assert_eq!(load_config(&path, false).unwrap(), Config::default());
+        assert!(load_config(&path, true).is_err());
+    }
+
+    #[test]
+    fn test_profile_connection() {
+        let timeout = Duration::from_secs(2);
+        let mut work = Profile {
+            endpoint: "https://anttp.example.com".to_string(),
+            token: Some("secret".to_string()),
+            ca_cert: None,
+        };
+        let connection = work.connection(timeout).unwrap();
+        assert_eq!(connection.endpoint, "https://anttp.example.com");
+        assert_eq!(connection.request_timeout, timeout);
+        assert!(connection.authorization.is_some());
+
+        let unusable = Profile { endpoint: "anttp.example.com".to_string(), ..work.clone() };
+        assert!(unusable.connection(timeout).is_err());
+        work.token = Some("bad\ntoken".to_string());
+        assert!(work.connection(timeout).is_err());
+        work.token = None;
+        work.ca_cert = Some(std::env::temp_dir().join("anttpmon-missing-ca.pem"));
+        let err = work.connection(timeout).err().unwrap();
+        assert!(format!("{err:#}").starts_with("failed to read CA certificate"));
+    }
+
+    #[test]
+    fn test_load_state_missing_or_unreadable() {
+        let dir = std::env::temp_dir().join(format!("anttpmon-state-{}", std::process::id()));
+        let path = dir.join("state.json");
+        assert_eq!(load_state(&path), None);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{not json").unwrap();
+        assert_eq!(load_state(&path), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remembered_state_overrides_config() {
+        let config = Config { filter_mode: Some(FilterMode::Aborted), ..Config::default() };
+        // the config file's filter mode applies until there is one remembered
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        apply_settings(&mut app, &config, None);
+        assert_eq!(app.state_filter, StateFilter::only(CommandState::Aborted));
+
+        let state = State { state_filter: StateFilter::ALL, ..State::default() };
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        apply_settings(&mut app, &config, Some(&state));
+        assert_eq!(app.state_filter, StateFilter::ALL);
+    }
+}
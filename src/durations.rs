@@ -0,0 +1,648 @@
+//! How long commands have been waiting and running, and how their times are shown.
+
+use std::time::Duration;
+use chrono::{LocalResult, TimeZone};
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::command::Command;
+use crate::app::CommandState;
+
+pub fn format_refresh_age(age: Option<Duration>) -> String {
+    match age {
+        Some(age) => format!("updated {}s ago", age.as_secs()),
+        None => "never updated".to_string(),
+    }
+}
+
+pub fn format_duration_ms(ms: u64) -> String {
+    let secs = ms as f64 / 1000.0;
+    format!("{secs:.3}")
+}
+
+/// Formats durations under a minute as seconds, and longer ones as whole `1h2m5s` style units.
+fn format_duration_human(ms: u64) -> String {
+    if ms < 60_000 {
+        return format!("{}s", format_duration_ms(ms));
+    }
+    let secs = (ms + 500) / 1000;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes}m{seconds}s")
+    } else {
+        format!("{minutes}m{seconds}s")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DurationFormat {
+    /// Seconds with millisecond precision, e.g. `3725.000`
+    Precise,
+    /// Hours, minutes and seconds, e.g. `1h2m5s`
+    Human,
+}
+
+impl DurationFormat {
+    pub fn format(&self, ms: u64) -> String {
+        match self {
+            DurationFormat::Precise => format_duration_ms(ms),
+            DurationFormat::Human => format_duration_human(ms),
+        }
+    }
+
+    /// Unit suffix for labelled values, as human-readable durations carry their own units.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            DurationFormat::Precise => " s",
+            DurationFormat::Human => "",
+        }
+    }
+
+    pub fn toggle(&self) -> DurationFormat {
+        match self {
+            DurationFormat::Precise => DurationFormat::Human,
+            DurationFormat::Human => DurationFormat::Precise,
+        }
+    }
+}
+
+/// Computes the waiting, running and completed/aborted durations in milliseconds.
+pub fn duration_millis(waiting_at: Option<u64>, running_at: Option<u64>, terminated_at: Option<u64>, now_ms: u64) -> (Option<u64>, Option<u64>, Option<u64>) {
+    // timestamps are expected to be normalized, with None for unset
+    // Waiting duration
+    let waiting = waiting_at.map(|w| running_at.unwrap_or(now_ms).saturating_sub(w));
+
+    // Running duration
+    let running = running_at.map(|r| terminated_at.unwrap_or(now_ms).saturating_sub(r));
+
+    // Completed/Aborted ago
+    let completed = terminated_at.map(|t| now_ms.saturating_sub(t));
+
+    (waiting, running, completed)
+}
+
+/// The server reports an unset timestamp as either absent or 0, depending on the field, so both
+/// are taken as unset: a 0 taken for an epoch would make for a bogus duration.
+pub fn normalize_epoch(ms: impl Into<Option<u64>>) -> Option<u64> {
+    ms.into().filter(|&ms| ms > 0)
+}
+
+/// A command's `waiting_at`, `running_at` and `terminated_at`, normalized.
+pub fn command_epochs(cmd: &Command) -> (Option<u64>, Option<u64>, Option<u64>) {
+    (
+        normalize_epoch(cmd.waiting_at),
+        normalize_epoch(cmd.running_at),
+        normalize_epoch(cmd.terminated_at),
+    )
+}
+
+/// A command's waiting and running durations, and how long ago it finished, in milliseconds.
+pub fn command_millis(cmd: &Command, now_ms: u64) -> (Option<u64>, Option<u64>, Option<u64>) {
+    let (waiting_at, running_at, terminated_at) = command_epochs(cmd);
+    duration_millis(waiting_at, running_at, terminated_at, now_ms)
+}
+
+pub fn compute_durations(waiting_at: Option<u64>, running_at: Option<u64>, terminated_at: Option<u64>, now_ms: u64, format: DurationFormat) -> (String, String, String) {
+    let (waiting, running, completed) = duration_millis(waiting_at, running_at, terminated_at, now_ms);
+    let format = |ms: Option<u64>| ms.map_or_else(|| "-".to_string(), |ms| format.format(ms));
+    (format(waiting), format(running), format(completed))
+}
+
+/// A pair of a command's timestamps in an order that can't happen, from clock skew or a bug.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimestampAnomaly {
+    RunningBeforeWaiting,
+    TerminatedBeforeRunning,
+    TerminatedBeforeWaiting,
+}
+
+impl TimestampAnomaly {
+    pub fn label(self) -> &'static str {
+        match self {
+            TimestampAnomaly::RunningBeforeWaiting => "running_at is before waiting_at",
+            TimestampAnomaly::TerminatedBeforeRunning => "terminated_at is before running_at",
+            TimestampAnomaly::TerminatedBeforeWaiting => "terminated_at is before waiting_at",
+        }
+    }
+}
+
+/// Checks that a command was queued, started and finished in that order, ignoring the
+/// timestamps that aren't set.
+pub fn timestamp_anomalies(cmd: &Command) -> Vec<TimestampAnomaly> {
+    let (waiting, running, terminated) = command_epochs(cmd);
+    let before = |later: Option<u64>, earlier: Option<u64>| {
+        later.zip(earlier).is_some_and(|(later, earlier)| later < earlier)
+    };
+    let mut anomalies = Vec::new();
+    if before(running, waiting) {
+        anomalies.push(TimestampAnomaly::RunningBeforeWaiting);
+    }
+    if before(terminated, running) {
+        anomalies.push(TimestampAnomaly::TerminatedBeforeRunning);
+    }
+    if before(terminated, waiting) {
+        anomalies.push(TimestampAnomaly::TerminatedBeforeWaiting);
+    }
+    anomalies
+}
+
+/// Whether a command that is still running has been running for longer than the threshold.
+pub fn is_long_running(cmd: &Command, threshold_ms: u64, now_ms: u64) -> bool {
+    if normalize_epoch(cmd.terminated_at).is_some() {
+        return false;
+    }
+    let (_, running, _) = command_millis(cmd, now_ms);
+    running.is_some_and(|ms| ms > threshold_ms)
+}
+
+/// How long a command is expected to run, in milliseconds, from its `expected_duration`
+/// property. Values that aren't a positive number of milliseconds are ignored.
+fn expected_duration_ms(cmd: &Command) -> Option<u64> {
+    let property = cmd.properties.iter().find(|p| p.name == "expected_duration")?;
+    let ms = property.value.trim().parse::<f64>().ok()?;
+    (ms.is_finite() && ms >= 1.0).then_some(ms as u64)
+}
+
+/// How far a command has run through its expected duration, from 0 to 1, or `None` if it
+/// hasn't started or has no expected duration. Overrunning commands stay at 1.
+pub fn running_progress(cmd: &Command, now_ms: u64) -> Option<f64> {
+    let expected = expected_duration_ms(cmd)?;
+    let (_, running, _) = command_millis(cmd, now_ms);
+    Some((running? as f64 / expected as f64).min(1.0))
+}
+
+/// How long a running command has left to go by its expected duration, in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Eta {
+    Remaining(u64),
+    Overdue(u64),
+}
+
+impl Eta {
+    pub fn label(self, format: DurationFormat) -> String {
+        match self {
+            Eta::Remaining(ms) => format!("{}{} left", format.format(ms), format.unit()),
+            Eta::Overdue(ms) => format!("overdue by {}{}", format.format(ms), format.unit()),
+        }
+    }
+}
+
+/// Estimates the time left from how long a command is expected to run and has been running.
+/// There is no estimate without an expected duration, and a zero one gives nothing to go by.
+fn estimate_eta(expected_ms: Option<u64>, elapsed_ms: u64) -> Option<Eta> {
+    let expected = expected_ms.filter(|&ms| ms > 0)?;
+    Some(match elapsed_ms.checked_sub(expected) {
+        Some(over) if over > 0 => Eta::Overdue(over),
+        _ => Eta::Remaining(expected.saturating_sub(elapsed_ms)),
+    })
+}
+
+/// The estimated time left of a command that is still running.
+pub fn command_eta(cmd: &Command, now_ms: u64) -> Option<Eta> {
+    if normalize_epoch(cmd.terminated_at).is_some() {
+        return None;
+    }
+    let (_, running, _) = command_millis(cmd, now_ms);
+    estimate_eta(expected_duration_ms(cmd), running?)
+}
+
+/// Which terminal timestamp ended a command.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Termination {
+    Completed,
+    Aborted,
+}
+
+impl Termination {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Termination::Completed => "Completed",
+            Termination::Aborted => "Aborted",
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        match self {
+            Termination::Completed => Style::default().fg(Color::Green),
+            Termination::Aborted => Style::default().fg(Color::Red),
+        }
+    }
+}
+
+/// A command's formatted times, shared by its table row and the details view.
+pub struct TimeCells {
+    pub waiting: String,
+    pub running: String,
+    pub terminated: String,
+    pub termination: Option<Termination>,
+    /// Whether it is still running, for longer than the long-running threshold.
+    pub long_running: bool,
+    pub anomalies: Vec<TimestampAnomaly>,
+}
+
+impl TimeCells {
+    pub fn running_style(&self) -> Style {
+        if self.long_running {
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        }
+    }
+
+    pub fn terminated_label(&self) -> &'static str {
+        self.termination.map_or("Completed/Aborted", |t| t.label())
+    }
+
+    pub fn terminated_style(&self) -> Style {
+        self.termination.map_or_else(Style::default, |t| t.style())
+    }
+
+    /// Warns of timestamps in an impossible order, which make the marked durations unreliable.
+    pub fn warning(&self) -> Option<String> {
+        if self.anomalies.is_empty() {
+            return None;
+        }
+        let labels: Vec<&str> = self.anomalies.iter().map(|a| a.label()).collect();
+        Some(format!("Warning: {}", labels.join(", ")))
+    }
+}
+
+/// Splits `terminated_at` into completed and aborted timestamps, as the server only reports one.
+fn split_terminated_at(cmd: &Command) -> (Option<u64>, Option<u64>) {
+    let terminated_at = normalize_epoch(cmd.terminated_at);
+    if CommandState::parse(&cmd.state) == CommandState::Aborted {
+        (None, terminated_at)
+    } else {
+        (terminated_at, None)
+    }
+}
+
+/// Picks the terminal timestamp, preferring `aborted_at` when both are present.
+fn termination(completed_at: Option<u64>, aborted_at: Option<u64>) -> Option<(u64, Termination)> {
+    match (aborted_at, completed_at) {
+        (Some(a), _) => Some((a, Termination::Aborted)),
+        (None, Some(c)) => Some((c, Termination::Completed)),
+        (None, None) => None,
+    }
+}
+
+/// Like `compute_durations`, but with separate completed and aborted timestamps, also returning
+/// which of them ended the command.
+fn compute_terminal_durations(
+    waiting_at: Option<u64>,
+    running_at: Option<u64>,
+    completed_at: Option<u64>,
+    aborted_at: Option<u64>,
+    now_ms: u64,
+    format: DurationFormat,
+) -> (String, String, String, Option<Termination>) {
+    let termination = termination(completed_at, aborted_at);
+    let (wait_str, run_str, comp_str) =
+        compute_durations(waiting_at, running_at, termination.map(|(t, _)| t), now_ms, format);
+    (wait_str, run_str, comp_str, termination.map(|(_, kind)| kind))
+}
+
+/// Formats a command's durations, as shown in the table, details view and exports.
+pub fn command_durations(
+    cmd: &Command,
+    now_ms: u64,
+    format: DurationFormat,
+) -> (String, String, String, Option<Termination>) {
+    let (completed_at, aborted_at) = split_terminated_at(cmd);
+    compute_terminal_durations(
+        normalize_epoch(cmd.waiting_at),
+        normalize_epoch(cmd.running_at),
+        completed_at,
+        aborted_at,
+        now_ms,
+        format,
+    )
+}
+
+fn format_timestamp<Tz: TimeZone>(ms: u64, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match tz.timestamp_millis_opt(ms as i64) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+            time.format("%H:%M:%S%.3f").to_string()
+        }
+        LocalResult::None => "-".to_string(),
+    }
+}
+
+/// Formats a command's `waiting_at`, `running_at` and `terminated_at` as wall-clock times.
+pub fn command_timestamps<Tz: TimeZone>(cmd: &Command, tz: &Tz) -> (String, String, String)
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let format =
+        |ms: Option<u64>| ms.map_or_else(|| "-".to_string(), |ms| format_timestamp(ms, tz));
+    let (waiting_at, running_at, terminated_at) = command_epochs(cmd);
+    (format(waiting_at), format(running_at), format(terminated_at))
+}
+
+/// The raw `waiting_at`, `running_at` and `terminated_at` epochs for the details view, each as
+/// milliseconds and as an ISO-8601 time, to correlate with server logs.
+pub fn epoch_lines<Tz: TimeZone>(cmd: &Command, tz: &Tz) -> [String; 3]
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let format = |label: &str, ms: Option<u64>| {
+        let time = ms.and_then(|ms| tz.timestamp_millis_opt(ms as i64).earliest());
+        match time {
+            Some(time) => {
+                let iso = time.format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+                format!("{label}: {} ({iso})", time.timestamp_millis())
+            }
+            None => format!("{label}: -"),
+        }
+    };
+    let (waiting_at, running_at, terminated_at) = command_epochs(cmd);
+    [
+        format("Waiting at", waiting_at),
+        format("Running at", running_at),
+        format("Terminated at", terminated_at),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::app::last_activity_ms;
+    use crate::command::{Property, command};
+
+    #[test]
+    fn test_format_refresh_age() {
+        assert_eq!(format_refresh_age(None), "never updated");
+        assert_eq!(format_refresh_age(Some(Duration::from_millis(2500))), "updated 2s ago");
+    }
+
+    #[test]
+    fn test_compute_terminal_durations() {
+        let now = 1_770_850_000u64;
+        let (w, r, c, t) = compute_terminal_durations(
+            Some(1_770_810_000),
+            Some(1_770_820_000),
+            None,
+            Some(1_770_830_000),
+            now,
+            DurationFormat::Precise,
+        );
+        assert_eq!((w.as_str(), r.as_str(), c.as_str()), ("10.000", "10.000", "20.000"));
+        assert_eq!(t, Some(Termination::Aborted));
+
+        let (_, _, c, t) = compute_terminal_durations(
+            Some(1_770_810_000),
+            Some(1_770_820_000),
+            Some(1_770_840_000),
+            None,
+            now,
+            DurationFormat::Precise,
+        );
+        assert_eq!(c, "10.000");
+        assert_eq!(t, Some(Termination::Completed));
+
+        let (_, r, c, t) = compute_terminal_durations(
+            Some(1_770_810_000),
+            Some(1_770_820_000),
+            None,
+            None,
+            now,
+            DurationFormat::Precise,
+        );
+        assert_eq!(r, "30.000");
+        assert_eq!(c, "-");
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn test_split_terminated_at_by_state() {
+        let mut cmd = command("a", "cmd", 1_000, Some(2_000));
+        cmd.terminated_at = Some(3_000);
+        cmd.state = "Aborted".to_string();
+        assert_eq!(split_terminated_at(&cmd), (None, Some(3_000)));
+        cmd.state = "completed".to_string();
+        assert_eq!(split_terminated_at(&cmd), (Some(3_000), None));
+    }
+
+    #[test]
+    fn test_is_long_running() {
+        let mut cmd = command("a", "cmd", 1_000, Some(2_000));
+        assert!(!is_long_running(&cmd, 60_000, 62_000));
+        assert!(is_long_running(&cmd, 60_000, 62_001));
+        // finished commands are not flagged, however long they ran
+        cmd.terminated_at = Some(100_000);
+        assert!(!is_long_running(&cmd, 60_000, 200_000));
+        // not yet running
+        let cmd = command("b", "cmd", 1_000, None);
+        assert!(!is_long_running(&cmd, 60_000, 200_000));
+    }
+
+    #[test]
+    fn test_expected_duration_ms() {
+        let mut cmd = command("a", "cmd", 1_000, Some(2_000));
+        assert_eq!(expected_duration_ms(&cmd), None);
+        for (value, expected) in [
+            ("60000", Some(60_000)),
+            (" 1500.5 ", Some(1_500)),
+            ("soon", None),
+            ("", None),
+            ("-5", None),
+            ("0", None),
+            ("NaN", None),
+            ("inf", None),
+        ] {
+            cmd.properties = vec![Property {
+                name: "expected_duration".to_string(),
+                value: value.to_string(),
+            }];
+            assert_eq!(expected_duration_ms(&cmd), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn test_running_progress() {
+        let mut cmd = command("a", "cmd", 1_000, Some(2_000));
+        assert_eq!(running_progress(&cmd, 5_000), None);
+        cmd.properties.push(Property {
+            name: "expected_duration".to_string(),
+            value: "4000".to_string(),
+        });
+        assert_eq!(running_progress(&cmd, 2_000), Some(0.0));
+        assert_eq!(running_progress(&cmd, 3_000), Some(0.25));
+        // overrunning is clamped to done
+        assert_eq!(running_progress(&cmd, 60_000), Some(1.0));
+        // a finished command keeps its final progress
+        cmd.terminated_at = Some(4_000);
+        assert_eq!(running_progress(&cmd, 60_000), Some(0.5));
+        let mut waiting = command("b", "cmd", 1_000, None);
+        waiting.properties = cmd.properties.clone();
+        assert_eq!(running_progress(&waiting, 5_000), None);
+    }
+
+    #[test]
+    fn test_estimate_eta() {
+        assert_eq!(estimate_eta(None, 1_000), None);
+        assert_eq!(estimate_eta(Some(0), 1_000), None);
+        assert_eq!(estimate_eta(Some(0), 0), None);
+        assert_eq!(estimate_eta(Some(5_000), 0), Some(Eta::Remaining(5_000)));
+        assert_eq!(estimate_eta(Some(5_000), 3_500), Some(Eta::Remaining(1_500)));
+        assert_eq!(estimate_eta(Some(5_000), 5_000), Some(Eta::Remaining(0)));
+        assert_eq!(estimate_eta(Some(5_000), 7_000), Some(Eta::Overdue(2_000)));
+        assert_eq!(Eta::Remaining(1_500).label(DurationFormat::Precise), "1.500 s left");
+        assert_eq!(Eta::Overdue(2_000).label(DurationFormat::Human), "overdue by 2.000s");
+        assert_eq!(Eta::Overdue(125_000).label(DurationFormat::Human), "overdue by 2m5s");
+    }
+
+    #[test]
+    fn test_command_eta() {
+        let mut cmd = command("a", "cmd", 1_000, Some(2_000));
+        assert_eq!(command_eta(&cmd, 5_000), None);
+        cmd.properties.push(Property {
+            name: "expected_duration".to_string(),
+            value: "4000".to_string(),
+        });
+        assert_eq!(command_eta(&cmd, 5_000), Some(Eta::Remaining(1_000)));
+        assert_eq!(command_eta(&cmd, 9_000), Some(Eta::Overdue(3_000)));
+        // nothing is left to estimate once it has finished
+        cmd.terminated_at = Some(9_000);
+        assert_eq!(command_eta(&cmd, 9_000), None);
+    }
+
+    #[test]
+    fn test_format_duration_human() {
+        assert_eq!(format_duration_human(0), "0.000s");
+        assert_eq!(format_duration_human(999), "0.999s");
+        assert_eq!(format_duration_human(59_999), "59.999s");
+        assert_eq!(format_duration_human(60_000), "1m0s");
+        assert_eq!(format_duration_human(61_499), "1m1s");
+        assert_eq!(format_duration_human(61_500), "1m2s");
+        assert_eq!(format_duration_human(3_600_000), "1h0m0s");
+        assert_eq!(format_duration_human(3_725_000), "1h2m5s");
+    }
+
+    #[test]
+    fn test_timestamp_anomalies() {
+        use TimestampAnomaly::*;
+        let at = |waiting_at, running_at, terminated_at| {
+            let mut cmd = command("a", "cmd", waiting_at, running_at);
+            cmd.terminated_at = terminated_at;
+            timestamp_anomalies(&cmd)
+        };
+        assert_eq!(at(1_000, Some(2_000), Some(3_000)), []);
+        assert_eq!(at(1_000, Some(1_000), Some(1_000)), []);
+        assert_eq!(at(2_000, Some(1_000), Some(3_000)), [RunningBeforeWaiting]);
+        assert_eq!(at(1_000, Some(3_000), Some(2_000)), [TerminatedBeforeRunning]);
+        assert_eq!(at(3_000, None, Some(2_000)), [TerminatedBeforeWaiting]);
+        assert_eq!(
+            at(3_000, Some(2_000), Some(1_000)),
+            [RunningBeforeWaiting, TerminatedBeforeRunning, TerminatedBeforeWaiting]
+        );
+        // unset timestamps are left out
+        assert_eq!(at(3_000, Some(0), Some(0)), []);
+        assert_eq!(at(0, Some(2_000), Some(1_000)), [TerminatedBeforeRunning]);
+    }
+
+    #[test]
+    fn test_compute_durations_human() {
+        let (w, r, c) =
+            compute_durations(Some(1_000), Some(3_726_000), None, 3_736_000, DurationFormat::Human);
+        assert_eq!(w, "1h2m5s");
+        assert_eq!(r, "10.000s");
+        assert_eq!(c, "-");
+    }
+
+    #[test]
+    fn test_command_timestamps() {
+        // 2026-02-11T21:51:38.575Z
+        let mut cmd = command("a", "cmd", 1_770_846_698_575, None);
+        cmd.terminated_at = Some(0);
+        let (w, r, c) = command_timestamps(&cmd, &Utc);
+        assert_eq!((w.as_str(), r.as_str(), c.as_str()), ("21:51:38.575", "-", "-"));
+        cmd.running_at = Some(1_770_846_700_000);
+        let offset = chrono::FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(command_timestamps(&cmd, &offset).1, "22:51:40.000");
+    }
+
+    #[test]
+    fn test_epoch_lines() {
+        let mut cmd = command("a", "cmd", 1_770_846_698_575, None);
+        cmd.terminated_at = Some(0);
+        let offset = chrono::FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            epoch_lines(&cmd, &offset),
+            [
+                "Waiting at: 1770846698575 (2026-02-11T22:51:38.575+01:00)",
+                "Running at: -",
+                "Terminated at: -",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_epoch() {
+        assert_eq!(normalize_epoch(0), None);
+        assert_eq!(normalize_epoch(1_000), Some(1_000));
+        assert_eq!(normalize_epoch(Some(0)), None);
+        assert_eq!(normalize_epoch(Some(1_000)), Some(1_000));
+        assert_eq!(normalize_epoch(None), None);
+
+        let mut cmd = command("a", "cmd", 0, Some(0));
+        cmd.terminated_at = Some(0);
+        assert_eq!(command_epochs(&cmd), (None, None, None));
+    }
+
+    #[test]
+    fn test_zero_timestamps_are_unset() {
+        // queued at 1s, but yet to start, with a 0 rather than no running_at
+        let mut cmd = command("a", "cmd", 1_000, Some(0));
+        cmd.terminated_at = Some(0);
+        assert_eq!(command_millis(&cmd, 10_000), (Some(9_000), None, None));
+        let (w, r, c, t) = command_durations(&cmd, 10_000, DurationFormat::Precise);
+        assert_eq!((w.as_str(), r.as_str(), c.as_str(), t), ("9.000", "-", "-", None));
+        assert!(!is_long_running(&cmd, 1_000, 10_000));
+        assert_eq!(command_timestamps(&cmd, &Utc).1, "-");
+        assert_eq!(last_activity_ms(&cmd), 1_000);
+
+        // and with a 0 waiting_at, nothing to measure from
+        let cmd = command("a", "cmd", 0, None);
+        assert_eq!(command_millis(&cmd, 10_000), (None, None, None));
+    }
+
+    #[test]
+    fn test_compute_durations_examples() {
+        // Example 1
+        let now = 1_770_846_698u64; // current time (ms epoch)
+        let waiting_at = Some(1_770_836_575u64);
+        let running_at = None;
+        let terminated_at = None;
+        let (w, r, c) =
+            compute_durations(waiting_at, running_at, terminated_at, now, DurationFormat::Precise);
+        assert_eq!(w, "10.123");
+        assert_eq!(r, "-");
+        assert_eq!(c, "-");
+
+        // Example 2
+        let now = 1_770_840_000u64;
+        let waiting_at = Some(1_770_810_000u64);
+        let running_at = Some(1_770_820_000u64);
+        let terminated_at = None;
+        let (w, r, c) =
+            compute_durations(waiting_at, running_at, terminated_at, now, DurationFormat::Precise);
+        assert_eq!(w, "10.000");
+        assert_eq!(r, "20.000");
+        assert_eq!(c, "-");
+
+        // Example 3
+        let now = 1_770_850_000u64;
+        let waiting_at = Some(1_770_810_000u64);
+        let running_at = Some(1_770_820_000u64);
+        let terminated_at = Some(1_770_830_000u64);
+        let (w, r, c) =
+            compute_durations(waiting_at, running_at, terminated_at, now, DurationFormat::Precise);
+        assert_eq!(w, "10.000");
+        assert_eq!(r, "10.000");
+        assert_eq!(c, "20.000");
+    }
+}
@@ -0,0 +1,88 @@
+//! Commands as CSV, to copy, and as a plain-text table, for `--once`.
+
+use crate::command::Command;
+use crate::durations::{DurationFormat, command_durations};
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders commands as CSV with the same columns as the table, using full IDs.
+pub fn commands_to_csv(commands: &[&Command], now_ms: u64) -> String {
+    let mut csv = String::from("ID,Name,State,Waiting,Running,Completed/Aborted\n");
+    for cmd in commands {
+        let (wait_str, run_str, comp_str, _) =
+            command_durations(cmd, now_ms, DurationFormat::Precise);
+        let fields = [&cmd.id, &cmd.name, &cmd.state, &wait_str, &run_str, &comp_str];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Renders commands as a plain-text table with space-aligned columns, for `--once`.
+pub fn commands_to_table(commands: &[&Command], now_ms: u64) -> String {
+    let header = ["ID", "Name", "State", "Waiting", "Running", "Completed/Aborted"];
+    let rows: Vec<[String; 6]> = commands
+        .iter()
+        .map(|cmd| {
+            let (wait_str, run_str, comp_str, _) =
+                command_durations(cmd, now_ms, DurationFormat::Precise);
+            [cmd.id.clone(), cmd.name.clone(), cmd.state.clone(), wait_str, run_str, comp_str]
+        })
+        .collect();
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    let mut table = String::new();
+    let header = header.map(String::from);
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> =
+            row.iter().zip(widths).map(|(field, width)| format!("{field:width$}")).collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::command;
+
+    #[test]
+    fn test_commands_to_table() {
+        let a = command("abc", "first", 1_000, Some(2_000));
+        let b = command("d", "second command", 4_000, None);
+        let table = commands_to_table(&[&a, &b], 5_000);
+        let expected = "\
+ID   Name            State    Waiting  Running  Completed/Aborted
+abc  first           waiting  1.000    3.000    -
+d    second command  waiting  1.000    -        -
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_commands_to_csv() {
+        let mut finished = command("abcdef0123456789", "Upload, \"big\"", 1_000, Some(2_000));
+        finished.state = "completed".to_string();
+        finished.terminated_at = Some(4_500);
+        let queued = command("b", "queued", 4_000, None);
+        let csv = commands_to_csv(&[&finished, &queued], 5_000);
+        assert_eq!(
+            csv,
+            "ID,Name,State,Waiting,Running,Completed/Aborted\n\
+             abcdef0123456789,\"Upload, \"\"big\"\"\",completed,1.000,2.500,0.500\n\
+             b,queued,waiting,1.000,-,-\n"
+        );
+    }
+}
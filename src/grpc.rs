@@ -0,0 +1,214 @@
+//! Talking to AntTP's command service over gRPC.
+
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::{Interceptor, interceptor::InterceptedService};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
+
+use crate::command::{Command, GetCommandsRequest};
+use crate::command::command_service_client::CommandServiceClient;
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type Client = CommandServiceClient<InterceptedService<Channel, AuthInterceptor>>;
+
+/// Describes a failed fetch, leading with the gRPC status code when there is one, which
+/// tells auth, connectivity and server errors apart.
+pub fn describe_error(err: &anyhow::Error) -> String {
+    match err.downcast_ref::<tonic::Status>() {
+        Some(status) => format!("{:?}: {}", status.code(), status.message()),
+        None => format!("{err:#}"),
+    }
+}
+
+/// Outcome of a background fetch, handing back the client for reuse.
+pub struct FetchResult {
+    pub client: Option<Client>,
+    pub result: Result<Vec<Command>>,
+    /// How long `get_commands` took to answer, if it did.
+    pub latency: Option<Duration>,
+}
+
+/// How to reach the AntTP instance.
+#[derive(Clone)]
+pub struct Connection {
+    pub endpoint: String,
+    /// Applies to connecting and to each request, which then fail like any other error.
+    pub request_timeout: Duration,
+    /// Trusted alongside the system roots for https endpoints.
+    pub ca_cert: Option<Certificate>,
+    /// The `authorization` header for bearer token authentication.
+    pub authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl Connection {
+    pub fn new(endpoint: String) -> Connection {
+        Connection {
+            endpoint,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            ca_cert: None,
+            authorization: None,
+        }
+    }
+
+    pub fn set_token(&mut self, token: &str) -> Result<()> {
+        // don't include the token in the error, as it ends up on screen
+        let mut value = MetadataValue::try_from(format!("Bearer {token}"))
+            .map_err(|_| anyhow::anyhow!("the token contains characters not allowed in a header"))?;
+        value.set_sensitive(true);
+        self.authorization = Some(value);
+        Ok(())
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let mut endpoint = Endpoint::from_shared(self.endpoint.clone())?
+            .connect_timeout(self.request_timeout)
+            .timeout(self.request_timeout);
+        if endpoint.uri().scheme_str() == Some("https") {
+            let mut tls = ClientTlsConfig::new().with_native_roots();
+            if let Some(ca_cert) = &self.ca_cert {
+                tls = tls.ca_certificate(ca_cert.clone());
+            }
+            endpoint = endpoint.tls_config(tls)?;
+        }
+        let channel = endpoint.connect().await?;
+        let auth = AuthInterceptor(self.authorization.clone());
+        Ok(CommandServiceClient::with_interceptor(channel, auth))
+    }
+}
+
+/// Attaches the `authorization` header, if any, to every request.
+#[derive(Clone)]
+pub struct AuthInterceptor(pub Option<MetadataValue<Ascii>>);
+
+impl Interceptor for AuthInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        if let Some(authorization) = &self.0 {
+            request.metadata_mut().insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Asks the server to abort a command. The command service has no abort RPC yet, so this
+/// fails without contacting the server; call the RPC here, with a client, once the proto has one.
+pub async fn abort_command(_id: &str) -> Result<(), tonic::Status> {
+    Err(tonic::Status::unimplemented("the command service has no abort RPC"))
+}
+
+pub fn abort_status(id: &str, result: Result<(), tonic::Status>) -> String {
+    match result {
+        Ok(()) => format!("Abort requested for {id}"),
+        Err(status) if status.code() == tonic::Code::Unimplemented => {
+            "Abort is not available: the command service has no abort RPC".to_string()
+        }
+        Err(status) => format!("Abort failed for {id}: {}", status.message()),
+    }
+}
+
+/// Connects if there is no client yet, then fetches the command list.
+pub async fn fetch_commands(
+    client: Option<Client>,
+    connection: &Connection,
+) -> FetchResult {
+    let mut client = match client {
+        Some(client) => client,
+        None => match connection.connect().await {
+            Ok(client) => client,
+            Err(err) => return FetchResult { client: None, result: Err(err), latency: None },
+        },
+    };
+    let mut request = tonic::Request::new(GetCommandsRequest {});
+    request.set_timeout(connection.request_timeout);
+    let started = Instant::now();
+    let response = client.get_commands(request).await;
+    let latency = started.elapsed();
+    match response {
+        Ok(response) => FetchResult {
+            client: Some(client),
+            result: Ok(response.into_inner().commands),
+            latency: Some(latency),
+        },
+        Err(status) => FetchResult {
+            // drop the client so the next tick reconnects
+            client: if is_transport_error(&status) { None } else { Some(client) },
+            result: Err(status.into()),
+            latency: None,
+        },
+    }
+}
+
+/// Checks that an endpoint is an http or https URL with a host, as `--endpoint` expects.
+pub fn validate_endpoint(endpoint: &str) -> Result<()> {
+    let uri: tonic::transport::Uri = endpoint.parse()?;
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        anyhow::bail!("the scheme must be http or https");
+    }
+    if uri.host().is_none_or(str::is_empty) {
+        anyhow::bail!("there is no host");
+    }
+    Ok(())
+}
+
+pub fn is_transport_error(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unavailable
+        || std::error::Error::source(status).is_some_and(|e| e.is::<tonic::transport::Error>())
+}
+
+/// Fetches the commands once, for the non-interactive modes.
+pub async fn fetch_once(connection: &Connection) -> Result<Vec<Command>> {
+    fetch_commands(None, connection)
+        .await
+        .result
+        .with_context(|| format!("failed to fetch commands from {}", connection.endpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_ENDPOINT;
+
+    #[test]
+    fn test_is_transport_error() {
+        assert!(is_transport_error(&tonic::Status::unavailable("connection refused")));
+        assert!(!is_transport_error(&tonic::Status::not_found("no such command")));
+        assert!(!is_transport_error(&tonic::Status::unimplemented("unsupported")));
+    }
+
+    #[test]
+    fn test_validate_endpoint() {
+        assert!(validate_endpoint("http://10.0.0.5:18887").is_ok());
+        assert!(validate_endpoint("https://anttp.example.com").is_ok());
+        assert!(validate_endpoint("10.0.0.5:18887").is_err());
+        assert!(validate_endpoint("ftp://10.0.0.5").is_err());
+        assert!(validate_endpoint("http://bad host").is_err());
+    }
+
+    #[test]
+    fn test_auth_interceptor() {
+        let mut connection = Connection::new(DEFAULT_ENDPOINT.to_string());
+        let mut interceptor = AuthInterceptor(connection.authorization.clone());
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert!(request.metadata().get("authorization").is_none());
+
+        connection.set_token("secret").unwrap();
+        let mut interceptor = AuthInterceptor(connection.authorization.clone());
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer secret");
+        assert!(connection.set_token("bad\ntoken").is_err());
+    }
+
+    #[test]
+    fn test_abort_status() {
+        assert_eq!(abort_status("1", Ok(())), "Abort requested for 1");
+        let unsupported = Err(tonic::Status::unimplemented("no"));
+        let unavailable = "Abort is not available: the command service has no abort RPC";
+        assert_eq!(abort_status("1", unsupported), unavailable);
+        let failed = Err(tonic::Status::not_found("gone"));
+        assert_eq!(abort_status("1", failed), "Abort failed for 1: gone");
+    }
+}
@@ -1,172 +1,274 @@
 use std::io;
-use std::time::{Duration, Instant};
-use anyhow::Result;
+use std::time::Duration;
+use anyhow::{Context, Result};
 use chrono::Utc;
+use clap::Parser;
 use crossterm::{
+    cursor,
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
-    Frame, Terminal,
+    layout::Position,
+    widgets::TableState,
+    Terminal,
 };
-use tonic::transport::Channel;
+use tonic::transport::Certificate;
 
 pub mod command {
     tonic::include_proto!("command");
+
+    /// A waiting command, for tests to fill in.
+    #[cfg(test)]
+    pub fn command(id: &str, name: &str, waiting_at: u64, running_at: Option<u64>) -> Command {
+        Command {
+            id: id.to_string(),
+            name: name.to_string(),
+            state: "waiting".to_string(),
+            waiting_at,
+            running_at,
+            ..Default::default()
+        }
+    }
 }
 
-use command::command_service_client::CommandServiceClient;
-use command::{Command, GetCommandsRequest};
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum FilterMode {
-    Default,   // Waiting or Running
-    Waiting,
-    Running,
-    Completed,
-    Aborted,
-    All,
+mod app;
+mod config;
+mod durations;
+mod export;
+mod grpc;
+mod snapshot;
+mod stats;
+mod ui;
+
+use command::Command;
+use app::{Action, App, CommandState, DEFAULT_REFRESH_INTERVAL, LogFile};
+use config::{
+    AbortAlert, Config, apply_settings, config_path, load_config, load_state, save_state,
+    state_path,
+};
+use durations::Termination;
+use export::commands_to_table;
+use grpc::{
+    Connection, DEFAULT_REQUEST_TIMEOUT, abort_command, abort_status, fetch_commands, fetch_once,
+    validate_endpoint,
+};
+use snapshot::{Snapshot, diff_snapshots, load_snapshot};
+use ui::{render_diff, render_panes};
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:18887";
+const ENDPOINT_ENV_VAR: &str = "ANTTP_ENDPOINT";
+const TOKEN_ENV_VAR: &str = "ANTTP_TOKEN";
+/// How often the UI redraws, so running timers count smoothly between data refreshes.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// How long to wait for input when no timers are running, as nothing needs redrawing until
+/// new data arrives.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Exit code for `--fail-on-aborted` when an aborted command is found.
+const ABORTED_EXIT_CODE: i32 = 2;
+/// Exit codes for `--wait`; a completed command exits with 0.
+const WAIT_ABORTED_EXIT_CODE: i32 = 1;
+const WAIT_TIMEOUT_EXIT_CODE: i32 = 3;
+
+#[derive(Parser)]
+#[command(version, about)]
+#[command(group(clap::ArgGroup::new("dump").args(["once", "json"])))]
+struct Args {
+    /// gRPC endpoint of the AntTP instance to monitor [default: http://localhost:18887]
+    #[arg(long)]
+    endpoint: Option<String>,
+    /// Print the current commands as a plain-text table and exit, without starting the TUI
+    #[arg(long)]
+    once: bool,
+    /// Print the current commands, with raw timestamps and properties, as JSON and exit
+    #[arg(long, conflicts_with = "once")]
+    json: bool,
+    /// With --once or --json, exit with code 2 if any command has been aborted
+    #[arg(long, requires = "dump")]
+    fail_on_aborted: bool,
+    /// Wait for the command with this full ID to finish, exiting 0 if it completes or 1 if
+    /// it is aborted
+    #[arg(long, value_name = "ID", conflicts_with = "dump")]
+    wait: Option<String>,
+    /// With --wait, give up after this many seconds, exiting with code 3
+    #[arg(long, value_name = "SECONDS", requires = "wait")]
+    timeout: Option<u64>,
+    /// Timeout for connecting to, and each request to, the AntTP instance
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = DEFAULT_REQUEST_TIMEOUT.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    request_timeout: u64,
+    /// PEM file with a CA certificate to trust for https endpoints, besides the system roots
+    #[arg(long, value_name = "PATH")]
+    ca_cert: Option<std::path::PathBuf>,
+    /// Bearer token sent with every request, overriding the ANTTP_TOKEN environment variable
+    #[arg(long)]
+    token: Option<String>,
+    /// Config file to read defaults from [default: anttpmon/config.toml in the config directory]
+    #[arg(long, value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+    /// Append failed refreshes and connection changes to this file, rotating it at 1 MiB
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+    /// Review a snapshot saved with `W` in the TUI, instead of connecting to an instance
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["dump", "wait"])]
+    replay: Option<std::path::PathBuf>,
+    /// Show which commands were added, removed or changed between two snapshots
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["dump", "wait", "replay"]
+    )]
+    diff: Option<Vec<std::path::PathBuf>>,
+    /// Ring the terminal bell, flash the title bar, or both, when a command aborts
+    #[arg(long, value_enum, value_name = "ALERT")]
+    alert_on_abort: Option<AbortAlert>,
+    /// Keep only this many commands, those with the latest activity, to bound memory use on
+    /// instances with a long history
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_commands: Option<u64>,
+    /// Watch a second instance in a pane alongside the first, with Tab switching between them
+    #[arg(long, value_name = "ENDPOINT", conflicts_with_all = ["dump", "wait", "replay", "diff"])]
+    compare: Option<String>,
 }
 
-struct App {
-    commands: Vec<Command>,
-    table_state: TableState,
-    filter_mode: FilterMode,
-    selected_command: Option<Command>,
-    client: Option<CommandServiceClient<Channel>>,
-    last_tick: Instant,
+/// Chooses the endpoint to connect to: CLI flag, then environment variable, then config file,
+/// then default.
+fn resolve_endpoint(flag: Option<String>, env: Option<String>, config: Option<String>) -> String {
+    flag.or(env.filter(|e| !e.is_empty()))
+        .or(config)
+        .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
 }
 
-impl App {
-    fn new(client: Option<CommandServiceClient<Channel>>) -> App {
-        App {
-            commands: Vec::new(),
-            table_state: TableState::default(),
-            filter_mode: FilterMode::Default,
-            selected_command: None,
-            client,
-            last_tick: Instant::now(),
-        }
-    }
+fn any_aborted(commands: &[Command]) -> bool {
+    commands.iter().any(|c| CommandState::parse(&c.state) == CommandState::Aborted)
+}
 
-    fn filtered_commands(&self) -> Vec<&Command> {
-        self.commands
-            .iter()
-            .filter(|c| {
-                let state = c.state.to_ascii_lowercase();
-                match self.filter_mode {
-                    FilterMode::Default => state == "waiting" || state == "running",
-                    FilterMode::Waiting => state == "waiting",
-                    FilterMode::Running => state == "running",
-                    FilterMode::Completed => state == "completed",
-                    FilterMode::Aborted => state == "aborted",
-                    FilterMode::All => true,
-                }
-            })
-            .collect()
+/// How the command with the given ID ended, or `None` if it is unknown or still in progress.
+fn command_outcome(commands: &[Command], id: &str) -> Option<Termination> {
+    let cmd = commands.iter().find(|c| c.id == id)?;
+    match CommandState::parse(&cmd.state) {
+        CommandState::Completed => Some(Termination::Completed),
+        CommandState::Aborted => Some(Termination::Aborted),
+        _ => None,
     }
+}
 
-    fn next(&mut self) {
-        let count = self.filtered_commands().len();
-        if count == 0 {
-            self.table_state.select(None);
-            return;
-        }
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if i >= count - 1 {
-                    0
-                } else {
-                    i + 1
+/// Polls the commands until the one with the given ID has finished. Fetch failures are
+/// reported and retried, as the instance may be restarting.
+async fn poll_until_finished(connection: &Connection, id: &str) -> Termination {
+    let mut client = None;
+    let mut interval = tokio::time::interval(DEFAULT_REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let fetch = fetch_commands(client.take(), connection).await;
+        client = fetch.client;
+        match fetch.result {
+            Ok(commands) => {
+                if let Some(outcome) = command_outcome(&commands, id) {
+                    return outcome;
                 }
             }
-            None => 0,
-        };
-        self.table_state.select(Some(i));
-    }
-
-    fn previous(&mut self) {
-        let count = self.filtered_commands().len();
-        if count == 0 {
-            self.table_state.select(None);
-            return;
-        }
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    count.saturating_sub(1)
-                } else {
-                    i - 1
-                }
+            Err(err) => {
+                eprintln!("failed to fetch commands from {}: {err}", connection.endpoint)
             }
-            None => 0,
-        };
-        self.table_state.select(Some(i));
-    }
-
-    async fn refresh_commands(&mut self) -> Result<()> {
-        if let Some(client) = &mut self.client {
-            let request = tonic::Request::new(GetCommandsRequest {});
-            let response = client.get_commands(request).await?;
-            self.commands = response.into_inner().commands;
         }
-        Ok(())
     }
 }
 
-fn format_id(id: &str) -> String {
-    if id.len() <= 6 {
-        id.to_string()
-    } else {
-        format!("{}..{}", &id[..3], &id[id.len() - 3..])
+/// Waits for a command to finish for `--wait`, returning the exit code.
+async fn wait_for_command(connection: &Connection, id: &str, timeout: Option<Duration>) -> i32 {
+    let poll = poll_until_finished(connection, id);
+    let outcome = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, poll).await.ok(),
+        None => Some(poll.await),
+    };
+    match outcome {
+        Some(Termination::Completed) => {
+            println!("Command {id} completed");
+            0
+        }
+        Some(Termination::Aborted) => {
+            println!("Command {id} aborted");
+            WAIT_ABORTED_EXIT_CODE
+        }
+        None => {
+            let secs = timeout.map_or(0, |t| t.as_secs());
+            println!("Timed out after {secs}s waiting for command {id}");
+            WAIT_TIMEOUT_EXIT_CODE
+        }
     }
 }
 
-fn format_duration_ms(ms: u64) -> String {
-    let secs = ms as f64 / 1000.0;
-    format!("{secs:.3}")
-}
-
-fn compute_durations(waiting_at: Option<u64>, running_at: Option<u64>, terminated_at: Option<u64>, now_ms: u64) -> (String, String, String) {
-    // Waiting duration
-    let waiting_str = match waiting_at.filter(|w| *w > 0) {
-        Some(w) => {
-            let end = running_at.filter(|r| *r > 0).unwrap_or(now_ms);
-            let dur = end.saturating_sub(w);
-            format_duration_ms(dur)
-        }
-        None => "-".to_string(),
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => load_config(path, true)?,
+        None => config_path().map_or_else(|| Ok(Config::default()), |p| load_config(&p, false))?,
     };
+    let endpoint = resolve_endpoint(
+        args.endpoint,
+        std::env::var(ENDPOINT_ENV_VAR).ok(),
+        config.endpoint.clone(),
+    );
+
+    let mut connection = Connection::new(endpoint);
+    connection.request_timeout = Duration::from_secs(args.request_timeout);
+    if let Some(path) = &args.ca_cert {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+        connection.ca_cert = Some(Certificate::from_pem(pem));
+    }
+    let token = args.token.or(std::env::var(TOKEN_ENV_VAR).ok());
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        connection.set_token(&token)?;
+    }
 
-    // Running duration
-    let running_str = match running_at.filter(|r| *r > 0) {
-        Some(r) => {
-            let end = terminated_at.filter(|t| *t > 0).unwrap_or(now_ms);
-            let dur = end.saturating_sub(r);
-            format_duration_ms(dur)
-        }
-        None => "-".to_string(),
+    if let Some(id) = args.wait {
+        let timeout = args.timeout.map(Duration::from_secs);
+        let code = wait_for_command(&connection, &id, timeout).await;
+        io::Write::flush(&mut io::stdout())?;
+        std::process::exit(code);
+    }
+    if let Some(endpoint) = &args.compare {
+        validate_endpoint(endpoint)
+            .with_context(|| format!("invalid endpoint to compare with {endpoint:?}"))?;
+    }
+    let snapshot = args.replay.as_deref().map(load_snapshot).transpose()?;
+    let diff = match args.diff.as_deref() {
+        Some([old, new]) => Some((load_snapshot(old)?, load_snapshot(new)?)),
+        _ => None,
     };
-
-    // Completed/Aborted ago
-    let completed_str = match terminated_at.filter(|t| *t > 0) {
-        Some(t) => {
-            let dur = now_ms.saturating_sub(t);
-            format_duration_ms(dur)
+    if args.once || args.json {
+        let commands = fetch_once(&connection).await?;
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&commands)?);
+        } else {
+            let now_ms = Utc::now().timestamp_millis() as u64;
+            let commands: Vec<&Command> = commands.iter().collect();
+            print!("{}", commands_to_table(&commands, now_ms));
         }
-        None => "-".to_string(),
-    };
+        if args.fail_on_aborted && any_aborted(&commands) {
+            io::Write::flush(&mut io::stdout())?;
+            std::process::exit(ABORTED_EXIT_CODE);
+        }
+        return Ok(());
+    }
 
-    (waiting_str, running_str, completed_str)
-}
+    // restore the terminal before the panic message is printed, or it is lost to the alt screen
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
 
-#[tokio::main]
-async fn main() -> Result<()> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -174,20 +276,53 @@ async fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let client = CommandServiceClient::connect("http://localhost:18887").await.ok();
-    let mut app = App::new(client);
+    if let Some((old, new)) = diff {
+        let res = run_diff(&mut terminal, &old, &new);
+        restore_terminal()?;
+        res?;
+        return Ok(());
+    }
+
+    // create app and run it, with a pane of its own for the instance to compare with
+    let state_path = state_path();
+    let state = state_path.as_deref().and_then(load_state);
+    let mut connections = vec![connection.clone()];
+    if let Some(endpoint) = args.compare {
+        // the same token and CA certificate, for another endpoint
+        connections.push(Connection { endpoint, ..connection });
+    }
+    let mut panes: Vec<App> = connections
+        .into_iter()
+        .map(|connection| {
+            let mut app = App::new(connection.endpoint.clone());
+            app.connection = connection;
+            apply_settings(&mut app, &config, state.as_ref());
+            if let Some(alert) = args.alert_on_abort {
+                app.alert_on_abort = Some(alert);
+            }
+            if let Some(max) = args.max_commands {
+                app.max_commands = Some(max as usize);
+            }
+            app.log_file = args.log_file.clone().map(LogFile::new);
+            app
+        })
+        .collect();
+    match (args.replay, snapshot) {
+        (Some(path), Some(snapshot)) => panes[0].start_replay(path, snapshot),
+        _ => panes.iter_mut().for_each(App::refresh_commands),
+    }
 
-    let res = run_app(&mut terminal, &mut app).await;
+    let res = run_app(&mut terminal, &mut panes).await;
 
     // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    restore_terminal()?;
+
+    // the first pane's layout is the one remembered
+    if let Some(path) = &state_path
+        && let Err(err) = save_state(path, &panes[0].state())
+    {
+        eprintln!("failed to save state to {}: {err:#}", path.display());
+    }
 
     if let Err(err) = res {
         println!("{:?}", err)
@@ -196,274 +331,175 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
-    let tick_rate = Duration::from_millis(1000);
+/// Waits at most one frame while timers are running, or longer when idle, but less if a
+/// refresh falls due sooner.
+fn poll_timeout(until_refresh: Option<Duration>, timers_running: bool) -> Duration {
+    let interval = if timers_running { FRAME_INTERVAL } else { IDLE_POLL_INTERVAL };
+    until_refresh.map_or(interval, |d| d.min(interval))
+}
+
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, cursor::Show)
+}
+
+async fn run_app<B: Backend>(terminal: &mut Terminal<B>, panes: &mut [App]) -> io::Result<()> {
+    let mut focus = 0;
     loop {
-        if app.last_tick.elapsed() >= tick_rate {
-            let _ = app.refresh_commands().await;
-            app.last_tick = Instant::now();
+        let mut redraw = false;
+        for (i, app) in panes.iter_mut().enumerate() {
+            app.poll_refresh();
+            if app.refresh_due().is_some_and(|d| d.is_zero()) {
+                app.refresh_commands();
+            }
+            app.focused = i == focus;
+            redraw |= app.needs_redraw();
         }
 
-        terminal.draw(|f| ui(f, app))?;
-
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if app.selected_command.is_some() {
-                    match key.code {
-                        KeyCode::Enter | KeyCode::Left | KeyCode::Backspace => {
-                            app.selected_command = None;
-                        }
-                        _ => {}
-                    }
-                    continue;
-                }
+        if redraw {
+            terminal.draw(|f| render_panes(f, panes))?;
+            panes.iter_mut().for_each(|app| app.dirty = false);
+        }
+        let mut ring_bell = false;
+        for app in panes.iter_mut() {
+            ring_bell |= std::mem::take(&mut app.ring_bell);
+        }
+        if ring_bell {
+            execute!(io::stdout(), crossterm::style::Print('\x07'))?;
+        }
 
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Down | KeyCode::Char('j') => app.next(),
-                    KeyCode::Up | KeyCode::Char('k') => app.previous(),
-                    KeyCode::Enter => {
-                        let filtered = app.filtered_commands();
-                        if let Some(index) = app.table_state.selected() {
-                            if let Some(cmd) = filtered.get(index) {
-                                app.selected_command = Some((*cmd).clone());
-                            }
-                        }
-                    }
-                    KeyCode::Char('w') => {
-                        app.filter_mode = FilterMode::Waiting;
-                        app.table_state.select(Some(0));
-                    }
-                    KeyCode::Char('r') => {
-                        app.filter_mode = FilterMode::Running;
-                        app.table_state.select(Some(0));
-                    }
-                    KeyCode::Char('c') => {
-                        app.filter_mode = FilterMode::Completed;
-                        app.table_state.select(Some(0));
-                    }
-                    KeyCode::Char('b') => {
-                        app.filter_mode = FilterMode::Aborted;
-                        app.table_state.select(Some(0));
-                    }
-                    KeyCode::Char('a') => {
-                        app.filter_mode = FilterMode::All;
-                        app.table_state.select(Some(0));
-                    }
-                    KeyCode::Char('d') => {
-                        app.filter_mode = FilterMode::Default;
-                        app.table_state.select(Some(0));
-                    }
-                    _ => {}
+        let timeout = panes
+            .iter()
+            .map(|app| poll_timeout(app.refresh_due(), app.is_animating()))
+            .min()
+            .unwrap_or(IDLE_POLL_INTERVAL);
+        if !event::poll(timeout)? {
+            continue;
+        }
+        let event = event::read()?;
+        if matches!(event, Event::Key(_) | Event::Mouse(_) | Event::Resize(_, _)) {
+            panes.iter_mut().for_each(|app| app.dirty = true);
+        }
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Mouse(mouse) => {
+                // the pane under the pointer takes the focus, unless the focused one is busy
+                let position = Position::new(mouse.column, mouse.row);
+                if !panes[focus].modal_open()
+                    && let Some(i) = panes.iter().position(|app| app.table_area.contains(position))
+                {
+                    focus = i;
                 }
+                let app = &mut panes[focus];
+                if !app.modal_open() {
+                    app.handle_mouse(mouse);
+                }
+                continue;
+            }
+            Event::Resize(_, _) => {
+                // repaint everything straight away, rather than diffing against the old size
+                terminal.autoresize()?;
+                terminal.clear()?;
+                continue;
             }
+            _ => continue,
+        };
+
+        if key.code == KeyCode::Tab && panes.len() > 1 && !panes[focus].modal_open() {
+            focus = (focus + 1) % panes.len();
+            continue;
+        }
+        let app = &mut panes[focus];
+        match app.handle_key(key.code) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Abort(id)) => {
+                let result = abort_command(&id).await;
+                app.set_status(abort_status(&id, result));
+            }
+            None => {}
         }
     }
 }
 
-fn ui(f: &mut Frame, app: &mut App) {
-    let rects = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0)].as_ref())
-        .split(f.area());
-
-    let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-    let normal_style = Style::default().bg(Color::Blue);
-    let header_cells = ["ID", "Name", "State", "Waiting", "Running", "Completed/Aborted"]
-        .iter()
-        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
-    let header = Row::new(header_cells)
-        .style(normal_style)
-        .height(1)
-        .bottom_margin(1);
-
-    let filtered = app.filtered_commands();
-    let now_ms = (Utc::now().timestamp_millis()) as u64;
-    let rows: Vec<Row> = filtered.iter().map(|item| {
-        let (wait_str, run_str, comp_str) = compute_durations(
-            if item.waiting_at > 0 { Some(item.waiting_at) } else { None },
-            item.running_at,
-            item.terminated_at,
-            now_ms,
-        );
-        let cells = vec![
-            Cell::from(format_id(&item.id)),
-            Cell::from(item.name.clone()),
-            Cell::from(item.state.clone()),
-            Cell::from(wait_str),
-            Cell::from(run_str),
-            Cell::from(comp_str),
-        ];
-        Row::new(cells).height(1)
-    }).collect();
-
-    let t = Table::new(
-        rows,
-        [
-            Constraint::Length(10),
-            Constraint::Min(20),
-            Constraint::Length(10),
-            Constraint::Length(12),
-            Constraint::Length(12),
-            Constraint::Length(18),
-        ],
-    )
-    .header(header)
-    .block(Block::default().borders(Borders::ALL).title(format!(
-        " AntTP Monitor - Mode: {} ",
-        match app.filter_mode {
-            FilterMode::Default => "Default (W/R)",
-            FilterMode::Waiting => "Waiting",
-            FilterMode::Running => "Running",
-            FilterMode::Completed => "Completed",
-            FilterMode::Aborted => "Aborted",
-            FilterMode::All => "All",
+/// Shows the differences between two snapshots until the user quits.
+fn run_diff<B: Backend>(
+    terminal: &mut Terminal<B>,
+    old: &Snapshot,
+    new: &Snapshot,
+) -> io::Result<()> {
+    let diffs = diff_snapshots(old, new);
+    let mut state = TableState::default().with_selected((!diffs.is_empty()).then_some(0));
+    let mut page_size = 1;
+    loop {
+        terminal.draw(|f| {
+            // borders, header and header margin leave the rest of the area for rows
+            page_size = f.area().height.saturating_sub(4).max(1) as usize;
+            render_diff(f, old, new, &diffs, &mut state);
+        })?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        let last = diffs.len().saturating_sub(1);
+        let selected = state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            KeyCode::PageDown => state.select(Some((selected + page_size).min(last))),
+            KeyCode::PageUp => state.select(Some(selected.saturating_sub(page_size))),
+            KeyCode::Home => state.select_first(),
+            KeyCode::End => state.select(Some(last)),
+            _ => {}
         }
-    )))
-    .row_highlight_style(selected_style)
-    .highlight_symbol(">> ");
-
-    f.render_stateful_widget(t, rects[0], &mut app.table_state);
-
-    if let Some(cmd) = &app.selected_command {
-        let block = Block::default()
-            .title(" Command Details ")
-            .borders(Borders::ALL)
-            .style(Style::default().bg(Color::Black));
-        let area = centered_rect(60, 60, f.area());
-        f.render_widget(Clear, area); //this clears out the background
-        f.render_widget(block, area);
-
-        let details_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(2)
-            .constraints(
-                [
-                    Constraint::Length(1), // ID
-                    Constraint::Length(1), // Name
-                    Constraint::Length(1), // State
-                    Constraint::Length(1), // Waiting
-                    Constraint::Length(1), // Running
-                    Constraint::Length(1), // Completed/Aborted
-                    Constraint::Length(1), // Empty
-                    Constraint::Length(1), // Properties Header
-                    Constraint::Min(0),    // Properties list
-                ]
-                .as_ref(),
-            )
-            .split(area);
-
-        f.render_widget(Paragraph::new(format!("ID: {}", cmd.id)), details_layout[0]);
-        f.render_widget(
-            Paragraph::new(format!("Name: {}", cmd.name)),
-            details_layout[1],
-        );
-        f.render_widget(
-            Paragraph::new(format!("State: {}", cmd.state)),
-            details_layout[2],
-        );
-        let now_ms = (Utc::now().timestamp_millis()) as u64;
-        let (wait_str, run_str, comp_str) = compute_durations(
-            if cmd.waiting_at > 0 { Some(cmd.waiting_at) } else { None },
-            cmd.running_at,
-            cmd.terminated_at,
-            now_ms,
-        );
-        f.render_widget(
-            Paragraph::new(format!("Waiting: {} s", wait_str)),
-            details_layout[3],
-        );
-        f.render_widget(
-            Paragraph::new(format!("Running: {} s", run_str)),
-            details_layout[4],
-        );
-        f.render_widget(
-            Paragraph::new(format!("Completed/Aborted: {} s", comp_str)),
-            details_layout[5],
-        );
-
-        f.render_widget(
-            Paragraph::new("Properties:").style(Style::default().add_modifier(Modifier::BOLD)),
-            details_layout[7],
-        );
-
-        let props_text: Vec<String> = cmd
-            .properties
-            .iter()
-            .map(|p| format!("{}: {}", p.name, p.value))
-            .collect();
-        let props_paragraph = Paragraph::new(props_text.join("\n")).wrap(Wrap { trim: true });
-        f.render_widget(props_paragraph, details_layout[8]);
     }
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Percentage((100 - percent_y) / 2),
-                Constraint::Percentage(percent_y),
-                Constraint::Percentage((100 - percent_y) / 2),
-            ]
-            .as_ref(),
-        )
-        .split(r);
-
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(
-            [
-                Constraint::Percentage((100 - percent_x) / 2),
-                Constraint::Percentage(percent_x),
-                Constraint::Percentage((100 - percent_x) / 2),
-            ]
-            .as_ref(),
-        )
-        .split(popup_layout[1])[1]
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::command;
+
+    #[test]
+    fn test_resolve_endpoint() {
+        let flag = Some("http://flag:1".to_string());
+        let env = Some("http://env:2".to_string());
+        let config = Some("http://config:3".to_string());
+        assert_eq!(resolve_endpoint(flag.clone(), env.clone(), config.clone()), "http://flag:1");
+        assert_eq!(resolve_endpoint(None, env, config.clone()), "http://env:2");
+        assert_eq!(resolve_endpoint(None, None, config.clone()), "http://config:3");
+        assert_eq!(resolve_endpoint(None, Some(String::new()), config), "http://config:3");
+        assert_eq!(resolve_endpoint(None, None, None), DEFAULT_ENDPOINT);
+        assert_eq!(resolve_endpoint(None, Some(String::new()), None), DEFAULT_ENDPOINT);
+    }
+
+    #[test]
+    fn test_any_aborted() {
+        let mut commands = vec![command("1", "a", 1_000, None), command("2", "b", 1_000, None)];
+        assert!(!any_aborted(&commands));
+        commands[1].state = "Aborted".to_string();
+        assert!(any_aborted(&commands));
+        assert!(!any_aborted(&[]));
+    }
 
     #[test]
-    fn test_format_id() {
-        assert_eq!(format_id("123456789"), "123..789");
-        assert_eq!(format_id("123"), "123");
-        assert_eq!(format_id("123456"), "123456");
+    fn test_command_outcome() {
+        let mut commands = vec![command("1", "a", 1_000, None), command("2", "b", 1_000, None)];
+        assert_eq!(command_outcome(&commands, "1"), None);
+        assert_eq!(command_outcome(&commands, "missing"), None);
+        commands[0].state = "Completed".to_string();
+        commands[1].state = "aborted".to_string();
+        assert_eq!(command_outcome(&commands, "1"), Some(Termination::Completed));
+        assert_eq!(command_outcome(&commands, "2"), Some(Termination::Aborted));
     }
 
     #[test]
-    fn test_compute_durations_examples() {
-        // Example 1
-        let now = 1_770_846_698u64; // current time (ms epoch)
-        let waiting_at = Some(1_770_836_575u64);
-        let running_at = None;
-        let terminated_at = None;
-        let (w, r, c) = compute_durations(waiting_at, running_at, terminated_at, now);
-        assert_eq!(w, "10.123");
-        assert_eq!(r, "-");
-        assert_eq!(c, "-");
-
-        // Example 2
-        let now = 1_770_840_000u64;
-        let waiting_at = Some(1_770_810_000u64);
-        let running_at = Some(1_770_820_000u64);
-        let terminated_at = None;
-        let (w, r, c) = compute_durations(waiting_at, running_at, terminated_at, now);
-        assert_eq!(w, "10.000");
-        assert_eq!(r, "20.000");
-        assert_eq!(c, "-");
-
-        // Example 3
-        let now = 1_770_850_000u64;
-        let waiting_at = Some(1_770_810_000u64);
-        let running_at = Some(1_770_820_000u64);
-        let terminated_at = Some(1_770_830_000u64);
-        let (w, r, c) = compute_durations(waiting_at, running_at, terminated_at, now);
-        assert_eq!(w, "10.000");
-        assert_eq!(r, "10.000");
-        assert_eq!(c, "20.000");
+    fn test_poll_timeout() {
+        assert_eq!(poll_timeout(None, true), FRAME_INTERVAL);
+        assert_eq!(poll_timeout(Some(Duration::from_secs(1)), true), FRAME_INTERVAL);
+        assert_eq!(poll_timeout(Some(Duration::from_millis(30)), true), Duration::from_millis(30));
+        assert_eq!(poll_timeout(Some(Duration::ZERO), true), Duration::ZERO);
+        assert_eq!(poll_timeout(None, false), IDLE_POLL_INTERVAL);
+        assert_eq!(poll_timeout(Some(Duration::from_secs(1)), false), IDLE_POLL_INTERVAL);
+        assert_eq!(poll_timeout(Some(Duration::from_millis(30)), false), Duration::from_millis(30));
     }
 }
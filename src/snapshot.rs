@@ -0,0 +1,161 @@
+//! Commands saved to a file to review later, and the differences between two of them.
+
+use std::collections::{HashMap, HashSet};
+use anyhow::{Context, Result};
+use ratatui::style::{Color, Style};
+
+use crate::command::Command;
+use crate::durations::command_millis;
+
+/// Writes a value out as pretty-printed JSON, as snapshots and the remembered state are.
+pub fn write_json<T: serde::Serialize + ?Sized>(
+    path: impl AsRef<std::path::Path>,
+    value: &T,
+) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// Commands saved to a file, to review later with `--replay`.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    /// When the commands were captured, in milliseconds since the epoch.
+    pub captured_at: u64,
+    pub endpoint: String,
+    pub commands: Vec<Command>,
+}
+
+pub fn load_snapshot(path: &std::path::Path) -> Result<Snapshot> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("invalid snapshot {}", path.display()))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl DiffKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiffKind::Added => "Added",
+            DiffKind::Removed => "Removed",
+            DiffKind::Changed => "Changed",
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        match self {
+            DiffKind::Added => Style::default().fg(Color::Green),
+            DiffKind::Removed => Style::default().fg(Color::Red),
+            DiffKind::Changed => Style::default().fg(Color::Yellow),
+        }
+    }
+}
+
+/// A command that differs between two snapshots, as it was in each of them.
+#[derive(Debug, PartialEq)]
+pub struct CommandDiff<'a> {
+    pub kind: DiffKind,
+    pub old: Option<&'a Command>,
+    pub new: Option<&'a Command>,
+}
+
+/// Matches the commands of two snapshots by id, listing those added, and those changed in state
+/// or durations, in the new snapshot's order, followed by those removed.
+pub fn diff_snapshots<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<CommandDiff<'a>> {
+    let old_by_id: HashMap<&str, &Command> =
+        old.commands.iter().map(|c| (c.id.as_str(), c)).collect();
+    let new_ids: HashSet<&str> = new.commands.iter().map(|c| c.id.as_str()).collect();
+    let durations = |cmd: &Command, captured_at: u64| command_millis(cmd, captured_at);
+    let mut diffs: Vec<CommandDiff> = new
+        .commands
+        .iter()
+        .filter_map(|cmd| match old_by_id.get(cmd.id.as_str()) {
+            None => Some(CommandDiff { kind: DiffKind::Added, old: None, new: Some(cmd) }),
+            Some(&before) => {
+                // how long ago a command finished always grows, so it isn't a change
+                let (old_waiting, old_running, _) = durations(before, old.captured_at);
+                let (new_waiting, new_running, _) = durations(cmd, new.captured_at);
+                let changed = !before.state.eq_ignore_ascii_case(&cmd.state)
+                    || old_waiting != new_waiting
+                    || old_running != new_running;
+                changed.then_some(CommandDiff {
+                    kind: DiffKind::Changed,
+                    old: Some(before),
+                    new: Some(cmd),
+                })
+            }
+        })
+        .collect();
+    diffs.extend(
+        old.commands
+            .iter()
+            .filter(|c| !new_ids.contains(c.id.as_str()))
+            .map(|c| CommandDiff { kind: DiffKind::Removed, old: Some(c), new: None }),
+    );
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_ENDPOINT;
+    use crate::command::command;
+
+    #[test]
+    fn test_load_snapshot_missing_file() {
+        let path = std::env::temp_dir().join("anttpmon-missing").join("snapshot.json");
+        let err = load_snapshot(&path).err().unwrap();
+        assert!(err.to_string().contains("failed to read snapshot"));
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let mut running = command("running", "cmd", 1_000, Some(2_000));
+        running.state = "running".to_string();
+        let mut finished = command("finished", "cmd", 1_000, Some(2_000));
+        finished.state = "completed".to_string();
+        finished.terminated_at = Some(3_000);
+        let old = Snapshot {
+            captured_at: 10_000,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            commands: vec![
+                command("gone", "cmd", 1_000, None),
+                running.clone(),
+                finished.clone(),
+                command("waiting", "cmd", 1_000, None),
+            ],
+        };
+        let mut now_finished = running.clone();
+        now_finished.state = "completed".to_string();
+        now_finished.terminated_at = Some(15_000);
+        let new = Snapshot {
+            captured_at: 20_000,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            commands: vec![
+                command("new", "cmd", 18_000, None),
+                now_finished,
+                finished,
+                command("waiting", "cmd", 1_000, None),
+            ],
+        };
+        let diffs: Vec<(DiffKind, &str)> = diff_snapshots(&old, &new)
+            .iter()
+            .map(|d| (d.kind, d.new.or(d.old).unwrap().id.as_str()))
+            .collect();
+        // the waiting command has waited longer, the finished one is unchanged
+        assert_eq!(
+            diffs,
+            [
+                (DiffKind::Added, "new"),
+                (DiffKind::Changed, "running"),
+                (DiffKind::Changed, "waiting"),
+                (DiffKind::Removed, "gone"),
+            ]
+        );
+    }
+}
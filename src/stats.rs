@@ -0,0 +1,199 @@
+//! Figures over the whole queue and the refreshes, for the statistics popup.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::app::{CommandState, NameStatsColumn};
+use crate::durations::{command_epochs, command_millis, duration_millis, is_long_running};
+
+/// The latest, shortest, mean and longest of some round trips, or `None` if there are none.
+pub fn latency_stats(latencies: &VecDeque<Duration>) -> Option<[Duration; 4]> {
+    let latest = *latencies.back()?;
+    let min = latencies.iter().min().copied()?;
+    let max = latencies.iter().max().copied()?;
+    let avg = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+    Some([latest, min, avg, max])
+}
+
+/// Aggregate figures over the whole queue, regardless of the active filter.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub total: usize,
+    pub waiting: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub aborted: usize,
+    /// Average and longest running time of the commands currently running.
+    pub avg_running_ms: Option<u64>,
+    pub max_running_ms: Option<u64>,
+    /// Running commands over the long-running threshold.
+    pub long_running: usize,
+}
+
+pub fn queue_stats(commands: &[Command], now_ms: u64, threshold_ms: u64) -> QueueStats {
+    let mut stats = QueueStats { total: commands.len(), ..Default::default() };
+    let mut running_total_ms = 0u64;
+    for cmd in commands {
+        match CommandState::parse(&cmd.state) {
+            CommandState::Waiting => stats.waiting += 1,
+            CommandState::Running => {
+                stats.running += 1;
+                let (waiting_at, running_at, _) = command_epochs(cmd);
+                let (_, running, _) = duration_millis(waiting_at, running_at, None, now_ms);
+                let running = running.unwrap_or(0);
+                running_total_ms += running;
+                stats.max_running_ms = stats.max_running_ms.max(Some(running));
+                if is_long_running(cmd, threshold_ms, now_ms) {
+                    stats.long_running += 1;
+                }
+            }
+            CommandState::Completed => stats.completed += 1,
+            CommandState::Aborted => stats.aborted += 1,
+            CommandState::Unknown => {}
+        }
+    }
+    if stats.running > 0 {
+        stats.avg_running_ms = Some(running_total_ms / stats.running as u64);
+    }
+    stats
+}
+
+/// Running times of the completed commands sharing a name.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NameStats {
+    pub name: String,
+    pub count: usize,
+    pub avg_running_ms: u64,
+    pub max_running_ms: u64,
+}
+
+/// Aggregates the running times of completed commands per name, sorted by `column`.
+pub fn name_stats(
+    commands: &[Command],
+    now_ms: u64,
+    column: NameStatsColumn,
+    ascending: bool,
+) -> Vec<NameStats> {
+    // name -> (count, total, longest)
+    let mut totals: HashMap<&str, (usize, u64, u64)> = HashMap::new();
+    for cmd in commands {
+        if CommandState::parse(&cmd.state) != CommandState::Completed {
+            continue;
+        }
+        let (_, running, _) = command_millis(cmd, now_ms);
+        let Some(running) = running else {
+            continue;
+        };
+        let (count, total, longest) = totals.entry(cmd.name.as_str()).or_default();
+        *count += 1;
+        *total += running;
+        *longest = (*longest).max(running);
+    }
+    let mut stats: Vec<NameStats> = totals
+        .into_iter()
+        .map(|(name, (count, total, longest))| NameStats {
+            name: name.to_string(),
+            count,
+            avg_running_ms: total / count as u64,
+            max_running_ms: longest,
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        let ordering = match column {
+            NameStatsColumn::Name => a.name.cmp(&b.name),
+            NameStatsColumn::Count => a.count.cmp(&b.count),
+            NameStatsColumn::Average => a.avg_running_ms.cmp(&b.avg_running_ms),
+            NameStatsColumn::Longest => a.max_running_ms.cmp(&b.max_running_ms),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        if ascending { ordering } else { ordering.reverse() }
+    });
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::command;
+
+    #[test]
+    fn test_latency_stats() {
+        assert_eq!(latency_stats(&VecDeque::new()), None);
+        let latencies = [30, 10, 20].map(Duration::from_millis).into();
+        let [latest, min, avg, max] = latency_stats(&latencies).unwrap();
+        assert_eq!([latest, min, avg, max].map(|d| d.as_millis()), [20, 10, 20, 30]);
+    }
+
+    #[test]
+    fn test_queue_stats() {
+        let mut commands = vec![
+            command("1", "a", 1_000, Some(2_000)),
+            command("2", "b", 1_000, Some(4_000)),
+            command("3", "c", 1_000, None),
+            command("4", "d", 1_000, Some(2_000)),
+            command("5", "e", 1_000, Some(2_000)),
+        ];
+        commands[0].state = "Running".to_string();
+        commands[1].state = "running".to_string();
+        commands[3].state = "completed".to_string();
+        commands[3].terminated_at = Some(3_000);
+        commands[4].state = "aborted".to_string();
+        let stats = queue_stats(&commands, 10_000, 7_000);
+        assert_eq!(
+            stats,
+            QueueStats {
+                total: 5,
+                waiting: 1,
+                running: 2,
+                completed: 1,
+                aborted: 1,
+                avg_running_ms: Some(7_000),
+                max_running_ms: Some(8_000),
+                long_running: 1,
+            }
+        );
+        assert_eq!(queue_stats(&commands, 10_000, 8_000).long_running, 0);
+        assert_eq!(queue_stats(&[], 10_000, 7_000), QueueStats::default());
+    }
+
+    #[test]
+    fn test_name_stats() {
+        let finished = |id: &str, name: &str, state: &str, running_at: u64, terminated_at: u64| {
+            let mut cmd = command(id, name, 500, Some(running_at));
+            cmd.state = state.to_string();
+            cmd.terminated_at = Some(terminated_at);
+            cmd
+        };
+        let commands = vec![
+            finished("1", "upload", "completed", 1_000, 4_000),
+            finished("2", "upload", "completed", 1_000, 2_000),
+            finished("3", "upload", "aborted", 1_000, 99_000),
+            finished("4", "download", "completed", 1_000, 2_500),
+            command("5", "download", 1_000, Some(1_000)),
+        ];
+        let stats = name_stats(&commands, 100_000, NameStatsColumn::Average, false);
+        assert_eq!(
+            stats,
+            vec![
+                NameStats {
+                    name: "upload".to_string(),
+                    count: 2,
+                    avg_running_ms: 2_000,
+                    max_running_ms: 3_000,
+                },
+                NameStats {
+                    name: "download".to_string(),
+                    count: 1,
+                    avg_running_ms: 1_500,
+                    max_running_ms: 1_500,
+                },
+            ]
+        );
+        let names: Vec<String> = name_stats(&commands, 100_000, NameStatsColumn::Name, true)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["download", "upload"]);
+    }
+}
@@ -0,0 +1,1789 @@
+//! Drawing the monitor: the command table, its popups and the snapshot diff.
+
+use std::time::Duration;
+use chrono::{Local, TimeZone};
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Cell, Clear, LineGauge, Paragraph, Row, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Sparkline, Table, TableState, Wrap,
+    },
+    Frame,
+};
+
+use crate::command::Command;
+use crate::app::{App, Column, CommandState, PendingAction, ViewRow, format_id, full_id_width};
+use crate::durations::{
+    DurationFormat, Eta, command_epochs, command_eta, compute_durations, epoch_lines,
+    format_duration_ms, format_refresh_age, running_progress,
+};
+use crate::snapshot::{CommandDiff, DiffKind, Snapshot};
+use crate::stats::{QueueStats, latency_stats, name_stats, queue_stats};
+
+/// Shown in turn in the title, one per frame, while a refresh is in flight.
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const STALE_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// How the selected row is drawn, instead of the theme's style. Colors are names such as
+/// `yellow` or `lightblue`, palette indices, or `#rrggbb`.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectionStyle {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    /// Any of bold, dim, italic, underlined, reversed and crossed_out.
+    pub modifiers: Vec<String>,
+}
+
+impl SelectionStyle {
+    /// Builds the style, leaving out any color or modifier it doesn't know, with a warning.
+    pub fn to_style(&self) -> (Style, Vec<String>) {
+        let mut style = Style::default();
+        let mut warnings = Vec::new();
+        let mut color = |name: &Option<String>, field: &str| {
+            let name = name.as_deref()?;
+            let color = name.parse::<Color>().ok();
+            if color.is_none() {
+                warnings.push(format!("unknown {field} color {name:?}"));
+            }
+            color
+        };
+        if let Some(fg) = color(&self.fg, "selection fg") {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = color(&self.bg, "selection bg") {
+            style = style.bg(bg);
+        }
+        for name in &self.modifiers {
+            match parse_modifier(name) {
+                Some(modifier) => style = style.add_modifier(modifier),
+                None => warnings.push(format!("unknown selection modifier {name:?}")),
+            }
+        }
+        (style, warnings)
+    }
+}
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    match name.to_ascii_lowercase().as_str() {
+        "bold" => Some(Modifier::BOLD),
+        "dim" => Some(Modifier::DIM),
+        "italic" => Some(Modifier::ITALIC),
+        "underlined" => Some(Modifier::UNDERLINED),
+        "reversed" => Some(Modifier::REVERSED),
+        "crossed_out" => Some(Modifier::CROSSED_OUT),
+        _ => None,
+    }
+}
+
+/// A built-in color palette, as set in the config file or cycled with `C`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    /// Hues told apart with the common forms of color blindness.
+    Colorblind,
+}
+
+impl ThemeName {
+    const ALL: [ThemeName; 4] =
+        [ThemeName::Dark, ThemeName::Light, ThemeName::HighContrast, ThemeName::Colorblind];
+
+    pub fn next(self) -> ThemeName {
+        let i = ThemeName::ALL.iter().position(|&t| t == self).unwrap_or(0);
+        ThemeName::ALL[(i + 1) % ThemeName::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemeName::Dark => "Dark",
+            ThemeName::Light => "Light",
+            ThemeName::HighContrast => "High contrast",
+            ThemeName::Colorblind => "Colorblind",
+        }
+    }
+
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Dark => Theme::DARK,
+            ThemeName::Light => Theme::LIGHT,
+            ThemeName::HighContrast => Theme::HIGH_CONTRAST,
+            ThemeName::Colorblind => Theme::COLORBLIND,
+        }
+    }
+}
+
+/// The colors the UI is drawn in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Theme {
+    /// The table header and the footer.
+    pub bar: Style,
+    /// Column headers, and keys in the help and confirmation dialogs.
+    pub label: Style,
+    /// The background of the details view and the other popups.
+    pub popup: Style,
+    pub selection: Style,
+    pub waiting: Style,
+    pub running: Style,
+    pub completed: Style,
+    pub aborted: Style,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        bar: Style::new().bg(Color::Blue),
+        label: Style::new().fg(Color::Yellow),
+        popup: Style::new().bg(Color::Black),
+        selection: Style::new().add_modifier(Modifier::REVERSED),
+        waiting: Style::new().fg(Color::Yellow),
+        running: Style::new().fg(Color::Green),
+        completed: Style::new().fg(Color::DarkGray),
+        aborted: Style::new().fg(Color::Red),
+    };
+    pub const LIGHT: Theme = Theme {
+        bar: Style::new().fg(Color::Black).bg(Color::LightBlue),
+        label: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+        popup: Style::new().fg(Color::Black).bg(Color::White),
+        selection: Style::new().add_modifier(Modifier::REVERSED),
+        waiting: Style::new().fg(Color::Rgb(0x9a, 0x67, 0x00)),
+        running: Style::new().fg(Color::Rgb(0x00, 0x7a, 0x00)),
+        completed: Style::new().fg(Color::Gray),
+        aborted: Style::new().fg(Color::Rgb(0xc0, 0x00, 0x00)),
+    };
+    pub const HIGH_CONTRAST: Theme = Theme {
+        bar: Style::new().fg(Color::White).bg(Color::Black).add_modifier(Modifier::BOLD),
+        label: Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+        popup: Style::new().fg(Color::White).bg(Color::Black),
+        selection: Style::new().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+        waiting: Style::new().fg(Color::LightYellow),
+        running: Style::new().fg(Color::LightGreen),
+        completed: Style::new().fg(Color::White),
+        aborted: Style::new().fg(Color::LightRed).add_modifier(Modifier::BOLD),
+    };
+    /// The dark theme with states in the Okabe-Ito palette.
+    pub const COLORBLIND: Theme = Theme {
+        waiting: Style::new().fg(Color::Rgb(0xe6, 0x9f, 0x00)),
+        running: Style::new().fg(Color::Rgb(0x56, 0xb4, 0xe9)),
+        completed: Style::new().fg(Color::Gray),
+        aborted: Style::new().fg(Color::Rgb(0xd5, 0x5e, 0x00)).add_modifier(Modifier::BOLD),
+        ..Theme::DARK
+    };
+
+    pub fn state(&self, state: CommandState) -> Style {
+        match state {
+            CommandState::Waiting => self.waiting,
+            CommandState::Running => self.running,
+            CommandState::Completed => self.completed,
+            CommandState::Aborted => self.aborted,
+            CommandState::Unknown => Style::default(),
+        }
+    }
+}
+
+/// Key bindings shown in the help overlay, with where the keys apply in brackets unless it's the
+/// table; keep in sync with the handlers in `App::handle_key`.
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("q", "Quit"),
+    ("?", "Show / hide this help"),
+    ("Up / k, Down / j", "Move selection up / down"),
+    ("PageUp / PageDown", "Move selection up / down by a page"),
+    ("Home / End, gg / G", "Jump to the first / last command"),
+    ("N / P", "Jump to the next / previous aborted command"),
+    ("Enter", "View details of the selected command"),
+    ("z", "Group commands by name"),
+    ("Enter (group)", "Collapse / expand the group"),
+    ("m", "Show / hide queue statistics"),
+    ("B", "Show / hide a bar chart of commands per state"),
+    ("T", "Show / hide timings by name (s / S to sort)"),
+    ("l", "Show / hide the log of failed refreshes (Up / Down to scroll)"),
+    ("Enter / Esc / Backspace (details)", "Close the details view"),
+    ("Right / n, Left / p (details)", "Show the next / previous command"),
+    ("Up / k, Down / j (details)", "Move the cursor through the properties"),
+    ("v (details)", "Copy the value of the property under the cursor"),
+    ("F5", "Refresh commands immediately"),
+    ("p", "Pause / resume automatic refresh"),
+    ("f", "Freeze / unfreeze the view at this point in time"),
+    ("F", "Follow the newest command, until moving the selection"),
+    ("+ / -", "Increase / decrease the refresh interval"),
+    ("] / [", "Increase / decrease the long-running threshold"),
+    ("C", "Cycle the color theme: dark, light, high contrast, colorblind"),
+    ("h", "Toggle precise / human-readable durations"),
+    ("t", "Toggle durations / absolute timestamps"),
+    ("i", "Toggle shortened / full IDs"),
+    ("o", "Toggle truncating / wrapping long names"),
+    ("1 - 6", "Show / hide the ID, Name, State, Waiting, Running, Completed column"),
+    ("/", "Search IDs, names and properties"),
+    ("Tab (search)", "Toggle searching substrings / names by regex"),
+    ("A", "Filter by age: <N or >N seconds since the latest activity"),
+    ("u", "Connect to another endpoint"),
+    ("U", "Pick a connection profile from the config file"),
+    ("Tab", "Switch panes, with --compare"),
+    ("s / S", "Cycle sort column / toggle sort direction"),
+    ("< / >", "Narrow / widen the sort column, or Name in server order"),
+    ("y", "Copy the selected command's ID"),
+    ("Y (details)", "Copy all of the command's details as text"),
+    ("x", "Abort the selected command, after confirming"),
+    ("e / E", "Export the current view to CSV / JSON"),
+    ("W", "Save a snapshot of all commands, to review with --replay"),
+    ("d", "Filter: Default (Waiting and Running)"),
+    ("w / r / c / b", "Filter: show / hide Waiting / Running / Completed / Aborted"),
+    ("a", "Filter: All"),
+];
+
+/// Returns the range of rows to show in a table `height` lines tall over `len` rows, starting
+/// from the previous `offset` and scrolling just enough to keep `selected` in view. Rows can take
+/// more than one line each, as given by `row_height`. At least one row is shown, even if it
+/// doesn't fit.
+pub fn visible_rows(
+    offset: usize,
+    selected: Option<usize>,
+    len: usize,
+    height: usize,
+    row_height: impl Fn(usize) -> usize,
+) -> (usize, usize) {
+    if len == 0 {
+        return (0, 0);
+    }
+    let mut start = offset.min(len - 1);
+    // pull the window back if there is space left below the last row
+    let mut used = 0;
+    for i in start..len {
+        used += row_height(i);
+        if used > height {
+            break;
+        }
+    }
+    while start > 0 && used + row_height(start - 1) <= height {
+        start -= 1;
+        used += row_height(start);
+    }
+    if let Some(selected) = selected {
+        if selected < start {
+            start = selected;
+        } else {
+            let mut used: usize = (start..=selected).map(&row_height).sum();
+            while start < selected && used > height {
+                used -= row_height(start);
+                start += 1;
+            }
+        }
+    }
+    let mut end = start + 1;
+    let mut used = row_height(start);
+    while end < len && used + row_height(end) <= height {
+        used += row_height(end);
+        end += 1;
+    }
+    (start, end)
+}
+
+/// The widths ratatui gives the columns of a table `width` cells wide inside its borders, with
+/// `selection_width` taken by the highlight symbol.
+fn column_widths(constraints: &[Constraint], width: u16, selection_width: u16) -> Vec<u16> {
+    let [_, columns_area] =
+        Layout::horizontal([Constraint::Length(selection_width), Constraint::Fill(0)])
+            .areas(Rect::new(0, 0, width, 1));
+    Layout::horizontal(constraints.to_vec())
+        .spacing(1)
+        .split(columns_area)
+        .iter()
+        .map(|r| r.width)
+        .collect()
+}
+
+/// Cuts a name to `width` characters, ending it with an ellipsis if anything was left out. A
+/// width of 0 leaves it whole.
+pub fn truncate_name(name: &str, width: u16) -> String {
+    let width = width as usize;
+    if width == 0 || name.chars().count() <= width {
+        return name.to_string();
+    }
+    let mut truncated: String = name.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Splits a name into lines of at most `width` characters, breaking after the last space, `/`
+/// or `-` on a line where there is one. A width of 0 leaves it on one line.
+pub fn wrap_name(name: &str, width: u16) -> Vec<String> {
+    let width = width as usize;
+    if width == 0 {
+        return vec![name.to_string()];
+    }
+    let mut lines = Vec::new();
+    let mut rest: Vec<char> = name.chars().collect();
+    while rest.len() > width {
+        let at = rest[..width]
+            .iter()
+            .rposition(|c| matches!(c, ' ' | '/' | '-'))
+            .map_or(width, |i| i + 1);
+        let line: String = rest.drain(..at).collect();
+        lines.push(line.trim_end().to_string());
+    }
+    lines.push(rest.into_iter().collect());
+    lines
+}
+
+/// The header text of each visible column, with an arrow by the sort column for its direction.
+pub fn header_labels(app: &App) -> Vec<String> {
+    app.columns
+        .iter()
+        .map(|&column| {
+            let header = column.header(app.absolute_times);
+            match app.sort_column {
+                Some(sorted) if sorted == column => {
+                    format!("{header} {}", if app.sort_ascending { "▲" } else { "▼" })
+                }
+                _ => header.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Builds a command's row, with a cell for each visible column.
+pub fn command_row(app: &App, item: &Command, now_ms: u64) -> Row<'static> {
+    let times = app.time_cells(item, now_ms);
+    let (running_style, terminated_style) = (times.running_style(), times.terminated_style());
+    let mut times = [Some(times.waiting), Some(times.running), Some(times.terminated)];
+    let cells: Vec<Cell> = app
+        .columns
+        .iter()
+        .map(|column| match column {
+            Column::Id => {
+                Cell::from(if app.full_ids { item.id.clone() } else { format_id(&item.id) })
+            }
+            Column::Name if app.wrap_names => {
+                Cell::from(wrap_name(&item.name, app.name_width).join("\n"))
+            }
+            Column::Name => Cell::from(truncate_name(&item.name, app.name_width)),
+            Column::State => Cell::from(state_label(&item.state)),
+            Column::Waiting => Cell::from(times[0].take().unwrap_or_default()),
+            Column::Running => Cell::from(times[1].take().unwrap_or_default()).style(running_style),
+            Column::Terminated => {
+                Cell::from(times[2].take().unwrap_or_default()).style(terminated_style)
+            }
+        })
+        .collect();
+    // briefly mark what the last refresh brought in
+    let change_modifier = match app.changes.get(&item.id) {
+        Some(DiffKind::Added) => Modifier::BOLD | Modifier::UNDERLINED,
+        Some(_) => Modifier::BOLD,
+        None => Modifier::empty(),
+    };
+    let style = app.theme().state(CommandState::parse(&item.state)).add_modifier(change_modifier);
+    let height = match app.wrap_names && app.columns.contains(&Column::Name) {
+        true => wrap_name(&item.name, app.name_width).len() as u16,
+        false => 1,
+    };
+    Row::new(cells).height(height).style(style)
+}
+
+/// A command's state as reported, after its marker.
+pub fn state_label(state: &str) -> String {
+    format!("{} {state}", CommandState::parse(state).marker())
+}
+
+/// Builds only the table rows that fit in `height` lines, scrolling the window like ratatui
+/// would to keep the selection visible, and returns the index of the first of them.
+pub fn window_rows(
+    app: &mut App,
+    selected: Option<usize>,
+    height: usize,
+    now_ms: u64,
+) -> (usize, Vec<Row<'static>>) {
+    let len = app.row_count();
+    let (start, end) =
+        visible_rows(app.table_state.offset(), selected, len, height, |i| app.row_height(i));
+    *app.table_state.offset_mut() = start;
+    // a page is as many rows as fit, which is fewer when names wrap
+    app.page_size = match app.wrap_names {
+        true => (end - start).max(1),
+        false => height,
+    };
+    let rows = app.rows()[start..end]
+        .iter()
+        .map(|row| match row {
+            ViewRow::Group { name, count, collapsed } => {
+                group_row(name, *count, *collapsed, &app.columns)
+            }
+            &ViewRow::Command(i) => command_row(app, &app.commands[i], now_ms),
+        })
+        .collect();
+    (start, rows)
+}
+
+/// Builds a group header row, labelled in the Name column, or the first one if that is hidden.
+fn group_row(name: &str, count: usize, collapsed: bool, columns: &[Column]) -> Row<'static> {
+    let marker = if collapsed { "▸" } else { "▾" };
+    let label_at = columns.iter().position(|&c| c == Column::Name).unwrap_or(0);
+    let mut cells = vec![Cell::from(""); label_at];
+    cells.push(Cell::from(format!("{marker} {name} ({count})")));
+    Row::new(cells).height(1).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+/// Draws each pane side by side, splitting the screen evenly between them.
+pub fn render_panes(f: &mut Frame, panes: &mut [App]) {
+    let constraints = vec![Constraint::Ratio(1, panes.len() as u32); panes.len()];
+    let areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(f.area());
+    for (app, area) in panes.iter_mut().zip(areas.iter()) {
+        ui(f, app, *area);
+    }
+}
+
+fn ui(f: &mut Frame, app: &mut App, area: Rect) {
+    // one point in time for everything drawn, so the table and details view agree
+    let now_ms = app.now_ms();
+    // commands can leave an age filter without a refresh, taking the selected row with them
+    app.expire_view(now_ms);
+    app.clamp_selection();
+    let prompting = app.searching || app.age_input.is_some() || app.endpoint_input.is_some();
+    let status_height = if prompting || app.status().is_some() { 1 } else { 0 };
+    let rects = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),                // Table
+                Constraint::Length(status_height), // Status line
+                Constraint::Length(1),             // Footer
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    if app.fetching {
+        app.spinner_frame = app.spinner_frame.wrapping_add(1);
+    }
+
+    let theme = app.theme();
+    let header_cells = header_labels(app).into_iter().map(|h| Cell::from(h).style(theme.label));
+    let header = Row::new(header_cells)
+        .style(theme.bar)
+        .height(1)
+        .bottom_margin(1);
+
+    // borders, header and header margin leave the rest of the area for rows
+    let body_height = rects[0].height.saturating_sub(4).max(1) as usize;
+    app.table_area = rects[0];
+
+    let len = app.row_count();
+    let selected = app.table_state.selected().filter(|&i| i < len);
+    // one width per visible column, matching the cells of each row
+    let widths: Vec<Constraint> =
+        app.columns.iter().map(|&c| c.constraint(app.column_width(c))).collect();
+    let selection_width = match selected {
+        Some(_) => Line::from(app.highlight_symbol.as_str()).width() as u16,
+        None => 0,
+    };
+    let column_widths = column_widths(&widths, rects[0].width.saturating_sub(2), selection_width);
+    app.name_width = match app.columns.iter().position(|&c| c == Column::Name) {
+        Some(i) => column_widths[i],
+        None => 0,
+    };
+
+    let (start, rows) = window_rows(app, selected, body_height, now_ms);
+    let end = start + rows.len();
+
+    let empty = app.filtered_len() == 0;
+    let title_style = if app.abort_flash.is_some() {
+        Style::default().bg(Color::Red)
+    } else {
+        Style::default()
+    };
+    // with panes side by side, the one not taking keys fades into the background
+    let border_style = match app.focused {
+        true => Style::default(),
+        false => Style::default().add_modifier(Modifier::DIM),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style)
+        .title(title(app))
+        .title_style(title_style);
+    let t = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .row_highlight_style(theme.selection)
+        .highlight_symbol(app.highlight_symbol.as_str());
+
+    let mut window_state = TableState::default().with_selected(selected.map(|i| i - start));
+    f.render_stateful_widget(t, rects[0], &mut window_state);
+    render_scrollbar(f, rects[0], start, end, len);
+    if empty && !app.is_connected() && app.replay.is_none() {
+        render_disconnected(f, app, rects[0]);
+    } else if empty {
+        render_empty_state(f, app, rects[0]);
+    }
+
+    let prompt = match (&app.endpoint_input, &app.age_input) {
+        (Some(input), _) => Some(format!("Endpoint: {input}")),
+        (_, Some(input)) => Some(format!("Age (<N younger or >N older than N seconds): {input}")),
+        _ => app.searching.then(|| match app.search_regex {
+            Some(_) => format!("Regex /{}", app.search_query),
+            None => format!("/{}", app.search_query),
+        }),
+    };
+    if let Some(prompt) = prompt {
+        f.set_cursor_position((rects[1].x + prompt.chars().count() as u16, rects[1].y));
+        let mut spans = vec![Span::raw(prompt)];
+        let search_prompt = app.age_input.is_none() && app.endpoint_input.is_none();
+        if let (true, Some(Err(error))) = (search_prompt, &app.search_regex) {
+            spans.push(Span::styled(format!("  {error}"), Style::default().fg(Color::Red)));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), rects[1]);
+    } else if let Some(status) = app.status() {
+        f.render_widget(Paragraph::new(status.to_string()), rects[1]);
+    }
+
+    render_footer(f, app, rects[2], now_ms);
+
+    if let Some(cmd) = &app.selected_command {
+        let block = Block::default()
+            .title(" Command Details ")
+            .borders(Borders::ALL)
+            .style(theme.popup);
+        let area = centered_rect(60, 60, area);
+        f.render_widget(Clear, area); //this clears out the background
+        f.render_widget(block, area);
+
+        let details_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Length(1), // ID
+                    Constraint::Length(1), // Name
+                    Constraint::Length(1), // State
+                    Constraint::Length(1), // Waiting
+                    Constraint::Length(1), // Running
+                    Constraint::Length(1), // Completed/Aborted
+                    Constraint::Length(1), // Waiting at
+                    Constraint::Length(1), // Running at
+                    Constraint::Length(1), // Terminated at
+                    Constraint::Length(1), // Progress, or empty
+                    Constraint::Length(1), // Time left, or empty
+                    Constraint::Length(1), // Timestamp warning, or empty
+                    Constraint::Length(1), // Properties Header
+                    Constraint::Min(0),    // Properties list
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        f.render_widget(Paragraph::new(format!("ID: {}", cmd.id)), details_layout[0]);
+        f.render_widget(
+            Paragraph::new(format!("Name: {}", cmd.name)),
+            details_layout[1],
+        );
+        f.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::raw("State: "),
+                Span::styled(state_label(&cmd.state), theme.state(CommandState::parse(&cmd.state))),
+            ])),
+            details_layout[2],
+        );
+        let times = app.time_cells(cmd, now_ms);
+        let unit = app.time_unit();
+        f.render_widget(
+            Paragraph::new(format!("Waiting: {}{unit}", times.waiting)),
+            details_layout[3],
+        );
+        f.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::raw("Running: "),
+                Span::styled(format!("{}{unit}", times.running), times.running_style()),
+            ])),
+            details_layout[4],
+        );
+        f.render_widget(
+            Paragraph::new(format!("{}: {}{unit}", times.terminated_label(), times.terminated))
+                .style(times.terminated_style()),
+            details_layout[5],
+        );
+        let epoch_style = Style::default().fg(Color::DarkGray);
+        for (line, area) in epoch_lines(cmd, &Local).into_iter().zip(&details_layout[6..9]) {
+            f.render_widget(Paragraph::new(line).style(epoch_style), *area);
+        }
+        if let Some(progress) = running_progress(cmd, now_ms) {
+            let gauge = LineGauge::default()
+                .label(format!("Progress: {:>3.0}% ", progress * 100.0))
+                .filled_style(Style::default().fg(Color::Green))
+                .ratio(progress);
+            f.render_widget(gauge, details_layout[9]);
+        }
+        if let Some(eta) = command_eta(cmd, now_ms) {
+            let style = match eta {
+                Eta::Remaining(_) => Style::default(),
+                Eta::Overdue(_) => Style::default().fg(Color::Red),
+            };
+            let eta = Paragraph::new(format!("ETA: {}", eta.label(app.duration_format)));
+            f.render_widget(eta.style(style), details_layout[10]);
+        }
+        if let Some(warning) = times.warning() {
+            let style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+            f.render_widget(Paragraph::new(warning).style(style), details_layout[11]);
+        }
+
+        f.render_widget(
+            Paragraph::new("Properties (Up/Down to select, v to copy the value):")
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+            details_layout[12],
+        );
+
+        let props_area = details_layout[13];
+        let selected_style = theme.selection;
+        let mut lines: Vec<Line> = Vec::new();
+        let mut heights = Vec::new();
+        for (i, text) in property_lines(cmd).into_iter().enumerate() {
+            // don't trim, or the indentation of pretty-printed JSON is lost
+            let wrapped = Paragraph::new(text.as_str()).wrap(Wrap { trim: false });
+            heights.push(wrapped.line_count(props_area.width) as u16);
+            let style = if i == app.selected_property { selected_style } else { Style::default() };
+            lines.extend(text.lines().map(|line| Line::styled(line.to_string(), style)));
+        }
+        // scroll here, as the wrapped heights are only known once the width is
+        let (selected, height) = (app.selected_property, props_area.height);
+        app.detail_scroll = scroll_to_property(&heights, selected, app.detail_scroll, height);
+        let props_paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
+        f.render_widget(props_paragraph.scroll((app.detail_scroll, 0)), props_area);
+    }
+
+    if app.show_stats {
+        render_stats(f, app, area, now_ms);
+    }
+
+    if app.show_state_chart {
+        render_state_chart(f, app, area, now_ms);
+    }
+
+    if app.show_name_stats {
+        render_name_stats(f, app, area, now_ms);
+    }
+
+    if app.show_profiles {
+        render_profiles(f, app, area);
+    }
+
+    if app.show_error_log {
+        render_error_log(f, app, area);
+    }
+
+    if app.show_help {
+        render_help(f, &theme, area);
+    }
+
+    if let Some(action) = &app.pending_action {
+        render_confirm(f, action, &theme, area);
+    }
+}
+
+const FOOTER_HINTS: &str = " q quit | ? help | / search | Enter details | w/r/c/b/a/d filters";
+
+pub fn footer_counts(matching: usize, total: usize) -> String {
+    format!("{matching}/{total} commands ")
+}
+
+/// Counts of all commands per state, whatever the filter, e.g. `W:12 R:3 C:540 A:2 | total 557`.
+pub fn state_summary(stats: &QueueStats) -> String {
+    format!(
+        "W:{} R:{} C:{} A:{} | total {} | ",
+        stats.waiting, stats.running, stats.completed, stats.aborted, stats.total
+    )
+}
+
+/// Says how many of the commands reported are kept, once `max_commands` has dropped some, as
+/// the counts, filters and sorts only go over those kept.
+pub fn retained_summary(retained: usize, reported: usize) -> String {
+    match reported > retained {
+        true => format!("showing {retained} of {reported} | "),
+        false => String::new(),
+    }
+}
+
+fn render_footer(f: &mut Frame, app: &App, area: Rect, now_ms: u64) {
+    let stats = queue_stats(&app.commands, now_ms, app.long_running_threshold_ms);
+    let retained = retained_summary(app.commands.len(), app.reported_commands);
+    let summary = state_summary(&stats);
+    let counts = footer_counts(app.filtered_len(), app.commands.len());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(retained.chars().count() as u16),
+                Constraint::Length(summary.len() as u16),
+                Constraint::Length(counts.len() as u16),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let style = app.theme().bar;
+    f.render_widget(Paragraph::new(FOOTER_HINTS).style(style), columns[0]);
+    let retained_style = style.add_modifier(Modifier::BOLD);
+    f.render_widget(Paragraph::new(retained).style(retained_style), columns[1]);
+    f.render_widget(Paragraph::new(summary).style(style), columns[2]);
+    f.render_widget(Paragraph::new(counts).style(style), columns[3]);
+}
+
+fn render_help(f: &mut Frame, theme: &Theme, area: Rect) {
+    let area = centered_rect(80, 70, area);
+    let key_width = KEY_BINDINGS.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    let rows: Vec<Row> = KEY_BINDINGS
+        .iter()
+        .map(|(key, action)| {
+            Row::new([
+                Cell::from(*key).style(theme.label),
+                Cell::from(*action),
+            ])
+        })
+        .collect();
+    let help = Table::new(rows, [Constraint::Length(key_width as u16 + 2), Constraint::Min(0)])
+        .block(
+            Block::default()
+                .title(" Help (? or Esc to close) ")
+                .borders(Borders::ALL)
+                .style(theme.popup),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(help, area);
+}
+
+pub fn render_diff(
+    f: &mut Frame,
+    old: &Snapshot,
+    new: &Snapshot,
+    diffs: &[CommandDiff],
+    state: &mut TableState,
+) {
+    let format = DurationFormat::Precise;
+    let durations = |cmd: &Command, captured_at: u64| {
+        let (waiting_at, running_at, terminated_at) = command_epochs(cmd);
+        compute_durations(waiting_at, running_at, terminated_at, captured_at, format)
+    };
+    // shows a value from each snapshot, or just the one if it is unchanged
+    let change = |old: Option<String>, new: Option<String>| match (old, new) {
+        (Some(old), Some(new)) if old != new => format!("{old} → {new}"),
+        (Some(value), _) | (None, Some(value)) => value,
+        (None, None) => String::new(),
+    };
+    let rows: Vec<Row> = diffs
+        .iter()
+        .map(|diff| {
+            let cmd = diff.new.or(diff.old).expect("a diff has at least one side");
+            let old_durations = diff.old.map(|c| durations(c, old.captured_at));
+            let new_durations = diff.new.map(|c| durations(c, new.captured_at));
+            Row::new([
+                Cell::from(diff.kind.label()),
+                Cell::from(cmd.id.clone()),
+                Cell::from(cmd.name.clone()),
+                Cell::from(change(
+                    diff.old.map(|c| c.state.clone()),
+                    diff.new.map(|c| c.state.clone()),
+                )),
+                Cell::from(change(
+                    old_durations.as_ref().map(|d| d.0.clone()),
+                    new_durations.as_ref().map(|d| d.0.clone()),
+                )),
+                Cell::from(change(old_durations.map(|d| d.1), new_durations.map(|d| d.1))),
+            ])
+            .style(diff.kind.style())
+        })
+        .collect();
+    let header = Row::new(["Change", "ID", "Name", "State", "Waiting (s)", "Running (s)"])
+        .style(Style::default().fg(Color::Yellow).bg(Color::Blue))
+        .bottom_margin(1);
+    let count = |kind: DiffKind| diffs.iter().filter(|d| d.kind == kind).count();
+    let captured = |snapshot: &Snapshot| {
+        Local
+            .timestamp_millis_opt(snapshot.captured_at as i64)
+            .single()
+            .map_or_else(|| "-".to_string(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+    };
+    let title = format!(
+        " AntTP Monitor - Diff of {} to {} - {} added, {} removed, {} changed (q to quit) ",
+        captured(old),
+        captured(new),
+        count(DiffKind::Added),
+        count(DiffKind::Removed),
+        count(DiffKind::Changed),
+    );
+    let ids: Vec<&Command> = diffs.iter().filter_map(|d| d.new.or(d.old)).collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(full_id_width(&ids)),
+            Constraint::Min(20),
+            Constraint::Length(22),
+            Constraint::Length(20),
+            Constraint::Length(20),
+        ],
+    )
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .highlight_symbol(">> ");
+    f.render_stateful_widget(table, f.area(), state);
+    if diffs.is_empty() {
+        let message = Paragraph::new("No differences between the snapshots")
+            .alignment(Alignment::Center)
+            .style(Style::default().add_modifier(Modifier::DIM));
+        let area = f.area();
+        let line = Rect::new(area.x + 1, area.y + area.height / 2, area.width.saturating_sub(2), 1);
+        f.render_widget(message, line);
+    }
+}
+
+/// Explains an empty table, telling an idle queue apart from one hidden by the filter or search.
+pub fn empty_state_message(total_commands: usize, searching: bool) -> &'static str {
+    if total_commands == 0 {
+        "No commands - the queue is idle"
+    } else if searching {
+        "No commands match the current filter and search (press 'a' for all, '/' to search again)"
+    } else {
+        "No commands match the current filter (press 'd' for default, 'a' for all)"
+    }
+}
+
+/// Draws a scrollbar over the right border of the table's rows, showing where the window from
+/// `start` to `end` sits among `len` rows. Nothing is drawn when every row fits.
+fn render_scrollbar(f: &mut Frame, table_area: Rect, start: usize, end: usize, len: usize) {
+    let shown = end - start;
+    if shown >= len {
+        return;
+    }
+    // below the top border, header and header margin, above the bottom border
+    let area = Rect::new(
+        table_area.x,
+        table_area.y + 3,
+        table_area.width,
+        table_area.height.saturating_sub(4),
+    );
+    // one position per window offset, so the thumb reaches the end with the last row
+    let mut state = ScrollbarState::new(len - shown + 1)
+        .position(start)
+        .viewport_content_length(shown);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_symbol(Some("│"));
+    f.render_stateful_widget(scrollbar, area, &mut state);
+}
+
+/// Centers the empty-state message in the rows area of the table.
+fn render_empty_state(f: &mut Frame, app: &App, table_area: Rect) {
+    let rows_height = table_area.height.saturating_sub(4);
+    if rows_height == 0 {
+        return;
+    }
+    let area = Rect::new(
+        table_area.x + 1,
+        table_area.y + 3 + rows_height / 2,
+        table_area.width.saturating_sub(2),
+        1,
+    );
+    let searching = !app.search_query.is_empty();
+    let message = Paragraph::new(empty_state_message(app.commands.len(), searching))
+        .alignment(Alignment::Center)
+        .style(Style::default().add_modifier(Modifier::DIM));
+    f.render_widget(message, area);
+}
+
+/// Replaces the empty table while disconnected, so an unreachable instance isn't mistaken
+/// for an idle one.
+fn render_disconnected(f: &mut Frame, app: &App, table_area: Rect) {
+    let (title, detail) = match &app.last_error {
+        Some(err) => (" Not connected to AntTP ", err.clone()),
+        None => (" Connecting to AntTP ", "Waiting for the first response...".to_string()),
+    };
+    let retry = if app.paused {
+        "Refresh is paused - press p to resume, or F5 to retry now".to_string()
+    } else {
+        let interval = format_duration_ms(app.refresh_interval.as_millis() as u64);
+        format!("Retrying every {interval}s - press F5 to retry now")
+    };
+    let lines = vec![
+        Line::from(app.connection.endpoint.clone())
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        Line::from(""),
+        Line::from(detail),
+        Line::from(""),
+        Line::from(retry).style(Style::default().add_modifier(Modifier::DIM)),
+    ];
+    let inner = Rect::new(
+        table_area.x + 1,
+        table_area.y + 3,
+        table_area.width.saturating_sub(2),
+        table_area.height.saturating_sub(4),
+    );
+    let area = centered_rect(70, 60, inner);
+    let panel = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        );
+    f.render_widget(panel, area);
+}
+
+fn render_confirm(f: &mut Frame, action: &PendingAction, theme: &Theme, area: Rect) {
+    // just tall enough for the prompt and the choices
+    let mut area = centered_rect(50, 100, area);
+    area.y += area.height.saturating_sub(5) / 2;
+    area.height = area.height.min(5);
+    let lines = vec![
+        Line::from(action.prompt()),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", theme.label),
+            Span::raw(format!(" {}   ", action.confirm_label())),
+            Span::styled("n", theme.label),
+            Span::raw(" cancel"),
+        ]),
+    ];
+    let dialog = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .title(" Confirm ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .style(theme.popup),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(dialog, area);
+}
+
+/// Lists the failed refreshes, newest first.
+fn render_error_log(f: &mut Frame, app: &mut App, area: Rect) {
+    let area = centered_rect(80, 70, area);
+    let theme = app.theme();
+    let lines: Vec<Line> = if app.error_log.is_empty() {
+        vec![Line::from("No errors").style(Style::default().add_modifier(Modifier::DIM))]
+    } else {
+        app.error_log
+            .iter()
+            .rev()
+            .map(|(at, message)| {
+                Line::from(vec![
+                    Span::styled(at.format("%H:%M:%S%.3f  ").to_string(), theme.label),
+                    Span::raw(message.clone()),
+                ])
+            })
+            .collect()
+    };
+    let log = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .title(" Error Log (l or Esc to close, Up/Down to scroll) ")
+            .borders(Borders::ALL)
+            .style(theme.popup),
+    );
+    let max_scroll =
+        (log.line_count(area.width.saturating_sub(2)) as u16).saturating_sub(area.height);
+    app.error_log_scroll = app.error_log_scroll.min(max_scroll);
+    f.render_widget(Clear, area);
+    f.render_widget(log.scroll((app.error_log_scroll, 0)), area);
+}
+
+fn render_stats(f: &mut Frame, app: &App, area: Rect, now_ms: u64) {
+    let area = centered_rect(50, 90, area);
+    let theme = app.theme();
+    let threshold_ms = app.long_running_threshold_ms;
+    let stats = queue_stats(&app.commands, now_ms, threshold_ms);
+    let duration = |ms: Option<u64>| {
+        let format = app.duration_format;
+        ms.map_or_else(|| "-".to_string(), |ms| format.format(ms) + format.unit())
+    };
+    let count = |state: CommandState, value: usize| {
+        Row::new([
+            Cell::from(format!("{} {}", state.marker(), state.label())).style(theme.state(state)),
+            Cell::from(value.to_string()),
+        ])
+    };
+    let mut rows = vec![
+        count(CommandState::Waiting, stats.waiting),
+        count(CommandState::Running, stats.running),
+        count(CommandState::Completed, stats.completed),
+        count(CommandState::Aborted, stats.aborted),
+        Row::new([Cell::from("Total"), Cell::from(stats.total.to_string())])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        Row::new([Cell::from(""), Cell::from("")]),
+        Row::new([Cell::from("Average running"), Cell::from(duration(stats.avg_running_ms))]),
+        Row::new([Cell::from("Longest running"), Cell::from(duration(stats.max_running_ms))]),
+        Row::new([Cell::from("Threshold"), Cell::from(duration(Some(threshold_ms)))]),
+        Row::new([Cell::from("Over threshold"), Cell::from(stats.long_running.to_string())])
+            .style(if stats.long_running > 0 {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            }),
+        Row::new([Cell::from(""), Cell::from("")]),
+    ];
+    let ms = |d: Duration| d.as_millis();
+    rows.extend(match latency_stats(&app.latencies) {
+        Some([latest, min, avg, max]) => [
+            Row::new([Cell::from("Latency"), Cell::from(format!("{} ms", ms(latest)))]),
+            Row::new([
+                Cell::from("Min / avg / max"),
+                Cell::from(format!(
+                    "{} / {} / {} ms over {}",
+                    ms(min),
+                    ms(avg),
+                    ms(max),
+                    app.latencies.len()
+                )),
+            ]),
+        ],
+        None => [
+            Row::new([Cell::from("Latency"), Cell::from("-")]),
+            Row::new([Cell::from("Min / avg / max"), Cell::from("-")]),
+        ],
+    });
+    // the figures come first, leaving the rest of the popup to the trend below
+    let table_height = rows.len() as u16 + 2;
+    let table = Table::new(rows, [Constraint::Length(17), Constraint::Min(0)]).block(
+        Block::default()
+            .title(" Queue Statistics (m or Esc to close) ")
+            .borders(Borders::ALL)
+            .style(theme.popup),
+    );
+    let rects = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(table_height), Constraint::Min(3)])
+        .split(area);
+    f.render_widget(Clear, area);
+    f.render_widget(table, rects[0]);
+    render_depth_trend(f, app, rects[1]);
+}
+
+/// Draws how many commands were waiting over the latest refreshes, as many as fit, so a
+/// growing or draining backlog shows at a glance.
+fn render_depth_trend(f: &mut Frame, app: &App, area: Rect) {
+    let history = &app.depth_history;
+    let shown = history.len().min(area.width.saturating_sub(2) as usize);
+    let samples: Vec<u64> = history.iter().skip(history.len() - shown).copied().collect();
+    let latest = samples.last().map_or_else(|| "-".to_string(), u64::to_string);
+    let title = format!(" Waiting: {latest}, over the last {} refreshes ", samples.len());
+    let sparkline = Sparkline::default()
+        .block(Block::default().title(title).borders(Borders::ALL).style(app.theme().popup))
+        .data(&samples)
+        .style(app.theme().state(CommandState::Waiting));
+    f.render_widget(sparkline, area);
+}
+
+/// How many commands are in each state, across all commands, in the order the filters go.
+pub fn state_counts(stats: &QueueStats) -> [(CommandState, u64); 4] {
+    [
+        (CommandState::Waiting, stats.waiting as u64),
+        (CommandState::Running, stats.running as u64),
+        (CommandState::Completed, stats.completed as u64),
+        (CommandState::Aborted, stats.aborted as u64),
+    ]
+}
+
+fn render_state_chart(f: &mut Frame, app: &App, area: Rect, now_ms: u64) {
+    let area = centered_rect(50, 60, area);
+    let theme = app.theme();
+    let stats = queue_stats(&app.commands, now_ms, app.long_running_threshold_ms);
+    let counts = state_counts(&stats);
+    let bars: Vec<Bar> = counts
+        .iter()
+        .map(|&(state, count)| {
+            let style = theme.state(state);
+            Bar::default()
+                .label(Line::from(state.label()))
+                .value(count)
+                .style(style)
+                .value_style(style.add_modifier(Modifier::REVERSED))
+        })
+        .collect();
+    // share the width out between the bars, leaving a column between each
+    let gaps = counts.len() as u16 - 1;
+    let bar_width = (area.width.saturating_sub(2 + gaps) / counts.len() as u16).max(1);
+    let title = format!(" Commands per State, {} in total (B or Esc to close) ", stats.total);
+    let chart = BarChart::default()
+        .block(Block::default().title(title).borders(Borders::ALL).style(theme.popup))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(1);
+    f.render_widget(Clear, area);
+    f.render_widget(chart, area);
+}
+
+fn render_name_stats(f: &mut Frame, app: &mut App, area: Rect, now_ms: u64) {
+    let area = centered_rect(70, 60, area);
+    let theme = app.theme();
+    let stats = name_stats(&app.commands, now_ms, app.name_stats_sort, app.name_stats_ascending);
+    let format = app.duration_format;
+    let duration = |ms: u64| format.format(ms) + format.unit();
+    let header = Row::new(["Name", "Count", "Average", "Longest"])
+        .style(theme.label)
+        .bottom_margin(1);
+    let rows: Vec<Row> = stats
+        .iter()
+        .map(|s| {
+            Row::new([
+                Cell::from(s.name.clone()),
+                Cell::from(s.count.to_string()),
+                Cell::from(duration(s.avg_running_ms)),
+                Cell::from(duration(s.max_running_ms)),
+            ])
+        })
+        .collect();
+    let direction = if app.name_stats_ascending { "asc" } else { "desc" };
+    let title = format!(
+        " Timings by Name - Sort: {} {direction} (T or Esc to close, s / S to sort) ",
+        app.name_stats_sort.label()
+    );
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(7),
+            Constraint::Length(14),
+            Constraint::Length(14),
+        ],
+    )
+    .header(header)
+    .row_highlight_style(theme.selection)
+    .block(Block::default().title(title).borders(Borders::ALL).style(theme.popup));
+    if app.name_stats_state.selected().is_some_and(|i| i >= stats.len()) {
+        app.name_stats_state.select(stats.len().checked_sub(1));
+    }
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.name_stats_state);
+}
+
+fn render_profiles(f: &mut Frame, app: &mut App, area: Rect) {
+    let area = centered_rect(60, 50, area);
+    let theme = app.theme();
+    let header = Row::new(["Profile", "Endpoint"]).style(theme.label).bottom_margin(1);
+    let rows: Vec<Row> = app
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            let active = app.active_profile.as_ref() == Some(name);
+            let marker = if active { "● " } else { "  " };
+            Row::new([format!("{marker}{name}"), profile.endpoint.clone()])
+        })
+        .collect();
+    let table = Table::new(rows, [Constraint::Length(20), Constraint::Min(20)])
+        .header(header)
+        .row_highlight_style(theme.selection)
+        .highlight_symbol(app.highlight_symbol.as_str())
+        .block(
+            Block::default()
+                .title(" Profiles (Enter to connect, U or Esc to close) ")
+                .borders(Borders::ALL)
+                .style(theme.popup),
+        );
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut app.profiles_state);
+}
+
+pub fn title(app: &App) -> Line<'static> {
+    let connection = if app.is_connected() {
+        Span::styled("[connected]", Style::default().fg(Color::Green))
+    } else {
+        Span::styled("[disconnected]", Style::default().fg(Color::Red))
+    };
+    let age = app.last_successful_refresh.map(|t| t.elapsed());
+    let age_style = if age.is_none_or(|a| a > STALE_THRESHOLD) {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let endpoint = &app.connection.endpoint;
+    let mut spans = vec![Span::raw(match &app.active_profile {
+        Some(profile) => format!(" AntTP Monitor - {profile} ({endpoint}) "),
+        None => format!(" AntTP Monitor - {endpoint} "),
+    })];
+    if let Some(path) = &app.replay {
+        spans.push(Span::styled(
+            format!("[replay of {}]", path.display()),
+            Style::default().fg(Color::Magenta),
+        ));
+    } else {
+        spans.extend([
+            connection,
+            Span::raw(" "),
+            Span::styled(format_refresh_age(age), age_style),
+            Span::raw(format!(
+                " every {}s",
+                format_duration_ms(app.refresh_interval.as_millis() as u64)
+            )),
+        ]);
+    }
+    if app.fetching {
+        let frame = SPINNER_FRAMES[app.spinner_frame % SPINNER_FRAMES.len()];
+        spans.push(Span::styled(format!(" {frame}"), Style::default().fg(Color::Cyan)));
+    }
+    if app.paused {
+        spans.push(Span::styled(
+            " PAUSED",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(frozen_at) = app.frozen_at {
+        let frozen_at = frozen_at.with_timezone(&Local);
+        let label = if app.replay.is_some() {
+            format!(" captured {}", frozen_at.format("%Y-%m-%d %H:%M:%S"))
+        } else {
+            format!(" FROZEN at {}", frozen_at.format("%H:%M:%S"))
+        };
+        spans.push(Span::styled(
+            label,
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::raw(format!(" - Mode: {} ", app.state_filter.label())));
+    if app.group_by_name {
+        spans.push(Span::raw("- Grouped by name "));
+    }
+    if app.follow {
+        spans.push(Span::raw("- Following "));
+    }
+    if !app.search_query.is_empty() {
+        let kind = if app.search_regex.is_some() { "Regex" } else { "Search" };
+        spans.push(Span::raw(format!("- {kind}: {} ", app.search_query)));
+    }
+    if let Some(age) = app.age_filter {
+        spans.push(Span::raw(format!("- Age: {} ", age.label())));
+    }
+    if let Some(column) = app.sort_column {
+        spans.push(Span::raw(format!(
+            "- Sort: {} {} ",
+            column.label(),
+            if app.sort_ascending { "asc" } else { "desc" }
+        )));
+    }
+    Line::from(spans)
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(popup_layout[1])[1]
+}
+
+/// Scrolls the property list of the details view as little as possible to show the selected
+/// property, given the wrapped height of each, or at least its start if it doesn't fit.
+pub fn scroll_to_property(heights: &[u16], selected: usize, scroll: u16, area_height: u16) -> u16 {
+    let Some(&height) = heights.get(selected) else {
+        return 0;
+    };
+    let start: u16 = heights[..selected].iter().sum();
+    let end = start + height;
+    if start < scroll {
+        start
+    } else if end > scroll + area_height {
+        end.saturating_sub(area_height).min(start)
+    } else {
+        scroll
+    }
+}
+
+/// A command's properties as `name: value` lines, for the details view.
+pub fn property_lines(cmd: &Command) -> Vec<String> {
+    cmd.properties
+        .iter()
+        .map(|p| format!("{}: {}", p.name, format_property_value(&p.value)))
+        .collect()
+}
+
+/// Pretty-prints property values holding a JSON object or array for the details view.
+/// Anything else, including values that fail to parse, is shown as-is.
+pub fn format_property_value(value: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(json) if json.is_object() || json.is_array() => {
+            serde_json::to_string_pretty(&json).unwrap_or_else(|_| value.to_string())
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use crossterm::event::KeyCode;
+    use ratatui::Terminal;
+    use crate::DEFAULT_ENDPOINT;
+    use crate::app::{Action, DEFAULT_LONG_RUNNING_THRESHOLD_MS, StateFilter};
+    use crate::command::{Property, command};
+    use crate::config::Config;
+
+    /// The presses of a key as the help overlay names it, with a sequence for each of a range.
+    fn key_presses(key: &str) -> Vec<Vec<KeyCode>> {
+        let code = match key {
+            "1 - 6" => return ('1'..='6').map(|c| vec![KeyCode::Char(c)]).collect(),
+            "gg" => return vec![vec![KeyCode::Char('g'); 2]],
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Backspace" => KeyCode::Backspace,
+            "Tab" => KeyCode::Tab,
+            "F5" => KeyCode::F(5),
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap()),
+            _ => panic!("unknown key {key:?}"),
+        };
+        vec![vec![code]]
+    }
+
+    /// A pane with something for every key to act on, in the context a binding names.
+    fn key_binding_app(context: Option<&str>) -> App {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut commands: Vec<Command> = (0..5)
+            .map(|i| {
+                // the odd names are too long to fit their column
+                let name = match i % 2 {
+                    0 => "upload".to_string(),
+                    _ => "archive-".repeat(12),
+                };
+                let mut cmd = command(&format!("{i}abcdef0123456789"), &name, 1_000, Some(2_000));
+                cmd.state = "running".to_string();
+                cmd.properties = ["path", "size", "type"]
+                    .map(|p| Property { name: p.to_string(), value: format!("{p}!") })
+                    .into();
+                cmd
+            })
+            .collect();
+        for i in [0, 4] {
+            commands[i].state = "aborted".to_string();
+            commands[i].terminated_at = Some(3_000);
+        }
+        app.set_commands(commands);
+        app.frozen_at = Utc.timestamp_millis_opt(10_000).single();
+        app.sort_column = Some(Column::Id);
+        app.table_state.select(Some(1));
+        match context {
+            None => {}
+            Some("details") => {
+                app.open_selected();
+                app.move_property_cursor(1);
+            }
+            Some("group") => {
+                app.group_by_name = true;
+                app.table_state.select(Some(0));
+            }
+            Some("search") => app.searching = true,
+            Some(context) => panic!("unknown context {context:?}"),
+        }
+        app
+    }
+
+    #[tokio::test]
+    async fn test_every_key_binding_does_something() {
+        let screen = |app: &mut App| {
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 30)).unwrap();
+            terminal.draw(|f| render_panes(f, std::slice::from_mut(app))).unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            buffer.content().iter().map(|cell| cell.symbol()).collect::<String>()
+        };
+        for (keys, action) in KEY_BINDINGS {
+            // a context such as `(details)` says where the keys apply
+            let (keys, context) = match keys.split_once(" (") {
+                Some((keys, context)) => (keys, Some(context.trim_end_matches(')'))),
+                None => (*keys, None),
+            };
+            for key in keys.split(", ").flat_map(|keys| keys.split(" / ")) {
+                // switching panes is up to the event loop, not a pane
+                if key == "Tab" && context.is_none() {
+                    continue;
+                }
+                for presses in key_presses(key) {
+                    let mut app = key_binding_app(context);
+                    let before = screen(&mut app);
+                    let mut result = None;
+                    for press in presses {
+                        result = app.handle_key(press);
+                    }
+                    let changed = screen(&mut app) != before;
+                    // exports and snapshots are written to the working directory
+                    let written = app.status().and_then(|s| s.rsplit(' ').next());
+                    if let Some(path) = written.filter(|p| p.starts_with("anttpmon-")) {
+                        let _ = std::fs::remove_file(path);
+                    }
+                    let context = context.map_or(String::new(), |c| format!(" in {c}"));
+                    assert!(
+                        result.is_some() || changed,
+                        "{key:?} does nothing{context}, bound to {action:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_selection_style_falls_back_on_unknown_names() {
+        let selection = SelectionStyle {
+            fg: Some("chartreuse".to_string()),
+            bg: Some("lightblue".to_string()),
+            modifiers: vec!["Italic".to_string(), "sparkly".to_string()],
+        };
+        let (style, warnings) = selection.to_style();
+        assert_eq!(style, Style::default().bg(Color::LightBlue).add_modifier(Modifier::ITALIC));
+        assert_eq!(
+            warnings,
+            ["unknown selection fg color \"chartreuse\"", "unknown selection modifier \"sparkly\""]
+        );
+
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.apply_config(&Config { selection_style: Some(selection), ..Config::default() });
+        assert!(app.status().is_some_and(|s| s.ends_with("\"sparkly\", using the default")));
+        assert_eq!(SelectionStyle::default().to_style(), (Style::default(), Vec::new()));
+    }
+
+    #[test]
+    fn test_header_labels_mark_sort_column() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.columns = vec![Column::Id, Column::Name, Column::Running];
+        assert_eq!(header_labels(&app), ["ID", "Name", "Running"]);
+        app.sort_column = Some(Column::Name);
+        assert_eq!(header_labels(&app), ["ID", "Name ▲", "Running"]);
+        app.sort_ascending = false;
+        app.sort_column = Some(Column::Running);
+        app.absolute_times = true;
+        assert_eq!(header_labels(&app), ["ID", "Name", "Running At ▼"]);
+        // sorting on a hidden column marks nothing
+        app.sort_column = Some(Column::State);
+        assert_eq!(header_labels(&app), ["ID", "Name", "Running At"]);
+    }
+
+    #[test]
+    fn test_scroll_to_property() {
+        let heights = [1, 3, 1, 1, 2];
+        // already in view
+        assert_eq!(scroll_to_property(&heights, 1, 0, 4), 0);
+        // below the view, scrolled to its last line
+        assert_eq!(scroll_to_property(&heights, 4, 0, 4), 4);
+        // above the view, scrolled to its first line
+        assert_eq!(scroll_to_property(&heights, 1, 4, 4), 1);
+        // taller than the view, from its start
+        assert_eq!(scroll_to_property(&heights, 1, 0, 2), 1);
+        assert_eq!(scroll_to_property(&[], 0, 3, 4), 0);
+    }
+
+    #[test]
+    fn test_format_property_value() {
+        assert_eq!(format_property_value("plain text"), "plain text");
+        assert_eq!(format_property_value("42"), "42");
+        assert_eq!(format_property_value("{\"broken\":"), "{\"broken\":");
+        assert_eq!(format_property_value("{\"a\":[1]}"), "{\n  \"a\": [\n    1\n  ]\n}");
+    }
+
+    #[test]
+    fn test_empty_state_message() {
+        assert!(empty_state_message(0, true).contains("idle"));
+        assert!(empty_state_message(3, false).contains("match the current filter ("));
+        assert!(empty_state_message(3, true).contains("filter and search"));
+    }
+
+    #[test]
+    fn test_spinner_advances_per_frame_while_fetching() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        assert_eq!(app.spinner_frame, 0);
+        app.fetching = true;
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        assert_eq!(app.spinner_frame, 2);
+        let title: String = title(&app).spans.iter().map(|span| span.content.as_ref()).collect();
+        assert!(title.contains(SPINNER_FRAMES[2]), "{title}");
+    }
+
+    #[test]
+    fn test_visible_window() {
+        let visible_window = |offset, selected, len, height| {
+            visible_rows(offset, selected, len, height, |_| 1).0
+        };
+        // selection inside the window keeps the offset
+        assert_eq!(visible_window(10, Some(15), 100, 20), 10);
+        // moving above or below the window scrolls just enough
+        assert_eq!(visible_window(10, Some(5), 100, 20), 5);
+        assert_eq!(visible_window(10, Some(30), 100, 20), 11);
+        // at the end of the list the window fills the screen
+        assert_eq!(visible_window(95, Some(99), 100, 20), 80);
+        // a list that shrank pulls the offset back
+        assert_eq!(visible_window(80, Some(3), 10, 20), 0);
+        assert_eq!(visible_window(0, None, 0, 20), 0);
+    }
+
+    #[test]
+    fn test_visible_rows_with_wrapped_rows() {
+        // every third row takes three lines
+        let height = |i: usize| if i.is_multiple_of(3) { 3 } else { 1 };
+        assert_eq!(visible_rows(0, Some(0), 10, 6, height), (0, 3));
+        // scrolling down drops rows from the top until the selection fits
+        assert_eq!(visible_rows(0, Some(5), 10, 6, height), (2, 6));
+        // a row taller than the screen is still shown
+        assert_eq!(visible_rows(0, Some(0), 10, 2, height), (0, 1));
+        // at the end of the list the window fills the screen
+        assert_eq!(visible_rows(9, Some(9), 10, 6, height), (7, 10));
+        assert_eq!(visible_rows(0, None, 0, 6, height), (0, 0));
+    }
+
+    #[test]
+    fn test_panes_side_by_side() {
+        let mut panes = vec![
+            App::new("http://node-a:18887".to_string()),
+            App::new("http://node-b:18887".to_string()),
+        ];
+        panes[1].focused = false;
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 12)).unwrap();
+        terminal.draw(|f| render_panes(f, &mut panes)).unwrap();
+        assert_eq!(panes[0].table_area, Rect::new(0, 0, 60, 11));
+        assert_eq!(panes[1].table_area, Rect::new(60, 0, 60, 11));
+        let buffer = terminal.backend().buffer();
+        let top: String = (0..120).map(|x| buffer[(x, 0)].symbol()).collect();
+        assert!(top.contains("node-a") && top.contains("node-b"), "{top}");
+        assert!(!buffer[(0, 0)].modifier.contains(Modifier::DIM));
+        assert!(buffer[(60, 0)].modifier.contains(Modifier::DIM));
+
+        // keys only reach the pane they are sent to
+        assert_eq!(panes[1].handle_key(KeyCode::Char('w')), None);
+        assert_eq!(panes[0].state_filter, StateFilter::DEFAULT);
+        assert_ne!(panes[1].state_filter, StateFilter::DEFAULT);
+        assert_eq!(panes[0].handle_key(KeyCode::Char('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn test_scrollbar_only_when_rows_overflow() {
+        fn right_edge(app: &mut App) -> String {
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(60, 12)).unwrap();
+            terminal.draw(|f| render_panes(f, std::slice::from_mut(app))).unwrap();
+            let buffer = terminal.backend().buffer();
+            (3..10).map(|y| buffer[(59, y)].symbol().to_string()).collect()
+        }
+        let commands = |count: usize| {
+            (0..count).map(|i| command(&i.to_string(), "upload", 1_000, None)).collect()
+        };
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.set_commands(commands(3));
+        assert_eq!(right_edge(&mut app), "│".repeat(7));
+
+        app.set_commands(commands(30));
+        app.table_state.select(Some(0));
+        let top = right_edge(&mut app);
+        assert!(top.starts_with('█') && top.ends_with('│'), "{top}");
+        app.table_state.select(Some(29));
+        let bottom = right_edge(&mut app);
+        assert!(bottom.starts_with('│') && bottom.ends_with('█'), "{bottom}");
+    }
+
+    #[test]
+    fn test_state_chart_with_empty_states() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        app.show_state_chart = true;
+        let mut running = command("b", "cmd", 1_000, Some(2_000));
+        running.state = "running".to_string();
+        app.set_commands(vec![command("a", "cmd", 1_000, None), running]);
+        let stats = queue_stats(&app.commands, 3_000, DEFAULT_LONG_RUNNING_THRESHOLD_MS);
+        let counts = state_counts(&stats).map(|(state, count)| (state.label(), count));
+        assert_eq!(counts, [("Waiting", 1), ("Running", 1), ("Completed", 0), ("Aborted", 0)]);
+
+        // states without commands, down to an empty queue, get an empty bar
+        for commands in [app.commands.to_vec(), Vec::new()] {
+            app.set_commands(commands);
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 20)).unwrap();
+            terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+            let buffer = terminal.backend().buffer();
+            let text: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+            assert!(text.contains("Completed") && text.contains("Aborted"), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_truncate_and_wrap_names() {
+        assert_eq!(truncate_name("upload", 10), "upload");
+        assert_eq!(truncate_name("upload-archive", 10), "upload-ar…");
+        assert_eq!(truncate_name("upload-archive", 0), "upload-archive");
+        assert_eq!(wrap_name("upload", 10), ["upload"]);
+        assert_eq!(wrap_name("upload archive/part", 10), ["upload", "archive/", "part"]);
+        assert_eq!(wrap_name("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_wrapped_names_keep_rows_in_step() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let name = "a".repeat(100);
+        app.set_commands((0..6).map(|i| command(&i.to_string(), &name, 1_000, None)).collect());
+        app.columns = vec![Column::Id, Column::Name];
+        app.table_state.select(Some(0));
+        app.wrap_names = true;
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(60, 12)).unwrap();
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+
+        // the name takes three lines of a 44 wide column, so two rows fit in seven lines
+        assert_eq!(app.name_width, 44);
+        assert_eq!(app.page_size, 2);
+        assert_eq!(app.row_at(20, 5), Some(0));
+        assert_eq!(app.row_at(20, 6), Some(1));
+        app.page_down();
+        app.page_down();
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        assert_eq!(app.table_state.selected(), Some(4));
+        assert_eq!(app.table_state.offset(), 3);
+        assert_eq!(app.row_at(20, 6), Some(4));
+
+        app.wrap_names = false;
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        let buffer = terminal.backend().buffer();
+        let row: String = (0..buffer.area.width).map(|x| buffer[(x, 3)].symbol()).collect();
+        assert!(row.contains(&format!("{}…", "a".repeat(43))), "{row}");
+    }
+
+    /// Out of 5,000 commands, only the rows filling `height` are built, around the selection.
+    #[test]
+    fn test_window_rows_builds_only_what_fits() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let commands = (0..5_000)
+            .map(|i| command(&format!("{i:016x}"), &format!("cmd{i}"), i, None))
+            .collect();
+        app.set_commands(commands);
+        let now_ms = 10_000;
+        let height = 40;
+        for selected in [0, 2_500, 4_999, 10] {
+            let (start, rows) = window_rows(&mut app, Some(selected), height, now_ms);
+            assert_eq!(rows.len(), height);
+            assert!((start..start + height).contains(&selected), "{start} for {selected}");
+        }
+        // wrapped names take more lines, so fewer rows fit
+        app.wrap_names = true;
+        app.name_width = 2;
+        let (_, rows) = window_rows(&mut app, Some(0), height, now_ms);
+        assert!(rows.len() < height, "{}", rows.len());
+    }
+
+    #[test]
+    fn test_footer_counts() {
+        assert_eq!(footer_counts(0, 0), "0/0 commands ");
+        assert_eq!(footer_counts(12, 557), "12/557 commands ");
+    }
+
+    #[test]
+    fn test_state_summary() {
+        let mut commands = vec![
+            command("1", "a", 1_000, None),
+            command("2", "a", 1_000, Some(2_000)),
+            command("3", "a", 1_000, Some(2_000)),
+            command("4", "a", 1_000, Some(2_000)),
+        ];
+        commands[1].state = "Running".to_string();
+        commands[2].state = "completed".to_string();
+        commands[3].state = "paused".to_string();
+        let stats = queue_stats(&commands, 10_000, DEFAULT_LONG_RUNNING_THRESHOLD_MS);
+        assert_eq!(state_summary(&stats), "W:1 R:1 C:1 A:0 | total 4 | ");
+    }
+
+    #[test]
+    fn test_state_style() {
+        let style = |state| Theme::DARK.state(CommandState::parse(state));
+        assert_eq!(style("running").fg, Some(Color::Green));
+        assert_eq!(style("Waiting").fg, Some(Color::Yellow));
+        assert_eq!(style("completed").fg, Some(Color::DarkGray));
+        assert_eq!(style("ABORTED").fg, Some(Color::Red));
+        assert_eq!(style("unknown"), Style::default());
+    }
+
+    #[test]
+    fn test_themes() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        assert_eq!(app.theme(), Theme::DARK);
+        app.cycle_theme();
+        assert_eq!(app.theme(), Theme::LIGHT);
+        assert_eq!(app.status(), Some("Theme: Light"));
+        app.cycle_theme();
+        assert_eq!(app.theme(), Theme::HIGH_CONTRAST);
+        app.cycle_theme();
+        assert_eq!(app.theme(), Theme::COLORBLIND);
+        app.cycle_theme();
+        assert_eq!(app.theme, ThemeName::Dark);
+
+        // a configured selection style outlasts theme changes
+        let selection = Style::default().fg(Color::Cyan);
+        app.apply_config(&Config {
+            theme: Some(ThemeName::Light),
+            selection_style: Some(SelectionStyle {
+                fg: Some("cyan".to_string()),
+                ..SelectionStyle::default()
+            }),
+            ..Config::default()
+        });
+        assert_eq!(app.theme(), Theme { selection, ..Theme::LIGHT });
+        app.cycle_theme();
+        assert_eq!(app.theme().selection, selection);
+    }
+
+    #[test]
+    fn test_state_label_marks_state() {
+        assert_eq!(state_label("waiting"), "… waiting");
+        assert_eq!(state_label("Running"), "▶ Running");
+        assert_eq!(state_label("completed"), "✓ completed");
+        assert_eq!(state_label("ABORTED"), "✗ ABORTED");
+        assert_eq!(state_label("paused"), "? paused");
+    }
+
+    #[test]
+    fn test_table_and_details_show_the_same_times() {
+        let mut app = App::new(DEFAULT_ENDPOINT.to_string());
+        let mut cmd = command("abc", "upload", 1_000, Some(3_500));
+        cmd.state = "running".to_string();
+        app.set_commands(vec![cmd]);
+        app.frozen_at = Utc.timestamp_millis_opt(10_250).single();
+        app.table_state.select(Some(0));
+        app.open_selected();
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|f| render_panes(f, std::slice::from_mut(&mut app))).unwrap();
+        let buffer = terminal.backend().buffer();
+        let lines: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        let times = app.time_cells(&app.commands[0], 10_250);
+        assert_eq!((times.waiting.as_str(), times.running.as_str()), ("2.500", "6.750"));
+        let row = lines.iter().find(|line| line.contains("upload")).unwrap();
+        assert!(row.contains(&times.waiting) && row.contains(&times.running), "{row}");
+        let shows = |text: String| lines.iter().any(|line| line.contains(&text));
+        assert!(shows(format!("Waiting: {} s", times.waiting)));
+        assert!(shows(format!("Running: {} s", times.running)));
+    }
+}